@@ -0,0 +1,2 @@
+ALTER TABLE accounts ADD COLUMN account_type TEXT NOT NULL DEFAULT 'checking';
+ALTER TABLE accounts ADD COLUMN liability BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE accounts ADD COLUMN archived BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,56 @@
+DROP TRIGGER tx_insert;
+
+CREATE TRIGGER tx_insert AFTER INSERT ON transactions
+BEGIN
+    UPDATE transactions
+    SET tx_order=(
+        SELECT COALESCE(MAX(tx_order), 0)+1
+        FROM transactions
+        WHERE account=NEW.account
+            AND tx_date=NEW.tx_date
+            AND transaction_id<>NEW.transaction_id
+    )
+    WHERE transaction_id=NEW.transaction_id;
+
+    UPDATE transactions SET accumulated=calc.acc FROM (
+        SELECT transaction_id, (
+            SUM(amount) OVER (
+                ORDER BY tx_date, tx_order, transaction_id
+                ROWS BETWEEN
+                UNBOUNDED PRECEDING
+                AND CURRENT ROW
+            )
+        ) acc
+        FROM transactions
+        WHERE account=NEW.account
+    ) AS calc
+    WHERE transactions.transaction_id=calc.transaction_id
+        AND transactions.tx_date>=NEW.tx_date;
+END;
+
+UPDATE transactions SET tx_order=calc.tx_order FROM (
+    SELECT transaction_id, (
+        ROW_NUMBER() OVER (
+            PARTITION BY account, tx_date
+            ORDER BY tx_order, transaction_id
+        )
+    ) tx_order
+    FROM transactions
+) AS calc
+WHERE transactions.transaction_id=calc.transaction_id;
+
+UPDATE transactions SET accumulated=calc.acc FROM (
+    SELECT transaction_id, (
+        SUM(amount) OVER (
+            PARTITION BY account
+            ORDER BY tx_date, tx_order, transaction_id
+            ROWS BETWEEN
+            UNBOUNDED PRECEDING
+            AND CURRENT ROW
+        )
+    ) acc
+    FROM transactions
+) AS calc
+WHERE transactions.transaction_id=calc.transaction_id;
+
+CREATE INDEX idx_transactions_order ON transactions(account, tx_date, tx_order);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS scheduled_transactions(
+    scheduled_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account INTEGER NOT NULL,
+    description TEXT,
+    category INTEGER,
+    amount INTEGER,
+    schedule TEXT NOT NULL,
+    next_date DATE NOT NULL,
+    end_date DATE,
+    FOREIGN KEY (account) REFERENCES accounts(account_id) ON DELETE CASCADE,
+    FOREIGN KEY (category) REFERENCES categories(category_id)
+);
+
+ALTER TABLE transactions ADD COLUMN scheduled INTEGER
+    REFERENCES scheduled_transactions(scheduled_id) ON DELETE SET NULL;
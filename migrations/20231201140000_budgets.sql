@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS budgets(
+    budget_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    category INTEGER NOT NULL UNIQUE,
+    amount INTEGER NOT NULL,
+    rollover BOOLEAN NOT NULL DEFAULT 0,
+    start_month DATE NOT NULL,
+    FOREIGN KEY (category) REFERENCES categories(category_id) ON DELETE CASCADE
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS goals(
+    goal_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    goal_name TEXT NOT NULL,
+    account INTEGER NOT NULL,
+    share INTEGER NOT NULL DEFAULT 100,
+    target_amount INTEGER NOT NULL,
+    target_date DATE NOT NULL,
+    FOREIGN KEY (account) REFERENCES accounts(account_id) ON DELETE CASCADE
+);
@@ -0,0 +1,30 @@
+CREATE TABLE IF NOT EXISTS securities(
+    security_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol TEXT NOT NULL UNIQUE,
+    security_name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS trades(
+    trade_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account INTEGER NOT NULL,
+    security INTEGER NOT NULL,
+    trade_date DATE NOT NULL,
+    kind TEXT NOT NULL,
+    quantity REAL NOT NULL DEFAULT 0,
+    price INTEGER NOT NULL DEFAULT 0,
+    fees INTEGER NOT NULL DEFAULT 0,
+    -- Cash moved by the trade: negative for buys, positive for sales and dividends.
+    amount INTEGER NOT NULL,
+    FOREIGN KEY (account) REFERENCES accounts(account_id) ON DELETE CASCADE,
+    FOREIGN KEY (security) REFERENCES securities(security_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_trades_account ON trades(account, trade_date);
+
+CREATE TABLE IF NOT EXISTS security_prices(
+    security INTEGER NOT NULL,
+    price_date DATE NOT NULL,
+    price INTEGER NOT NULL,
+    PRIMARY KEY (security, price_date),
+    FOREIGN KEY (security) REFERENCES securities(security_id) ON DELETE CASCADE
+);
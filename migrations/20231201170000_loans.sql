@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS loans(
+    loan_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account INTEGER NOT NULL,
+    principal INTEGER NOT NULL,
+    -- Nominal annual interest rate, as a percentage.
+    annual_rate REAL NOT NULL,
+    term_months INTEGER NOT NULL,
+    -- Date of the first installment.
+    start_date DATE NOT NULL,
+    payment_account INTEGER,
+    FOREIGN KEY (account) REFERENCES accounts(account_id) ON DELETE CASCADE,
+    FOREIGN KEY (payment_account) REFERENCES accounts(account_id) ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS loan_payments(
+    loan INTEGER NOT NULL,
+    installment INTEGER NOT NULL,
+    transaction_id INTEGER NOT NULL UNIQUE,
+    PRIMARY KEY (loan, installment),
+    FOREIGN KEY (loan) REFERENCES loans(loan_id) ON DELETE CASCADE,
+    FOREIGN KEY (transaction_id) REFERENCES transactions(transaction_id) ON DELETE CASCADE
+);
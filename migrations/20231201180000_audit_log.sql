@@ -0,0 +1,205 @@
+-- Every change to accounts, categories, rules and transactions is recorded by the
+-- triggers below, so that nothing can bypass the log. The whole row is stored as JSON.
+-- Connections have no notion of the user doing the change, so user_id stays empty until
+-- requests are authenticated.
+CREATE TABLE IF NOT EXISTS audit_log(
+    audit_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_name TEXT NOT NULL,
+    row_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    user_id INTEGER,
+    changed_at DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_row ON audit_log(table_name, row_id);
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_insert AFTER INSERT ON accounts
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('accounts', NEW.account_id, 'create',
+    json_object(
+        'account_id', NEW.account_id,
+        'account_name', NEW.account_name,
+        'account_type', NEW.account_type,
+        'liability', NEW.liability,
+        'archived', NEW.archived
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_update AFTER UPDATE ON accounts
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('accounts', NEW.account_id, 'update',
+    json_object(
+        'account_id', OLD.account_id,
+        'account_name', OLD.account_name,
+        'account_type', OLD.account_type,
+        'liability', OLD.liability,
+        'archived', OLD.archived
+    ),
+    json_object(
+        'account_id', NEW.account_id,
+        'account_name', NEW.account_name,
+        'account_type', NEW.account_type,
+        'liability', NEW.liability,
+        'archived', NEW.archived
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_delete AFTER DELETE ON accounts
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('accounts', OLD.account_id, 'delete',
+    json_object(
+        'account_id', OLD.account_id,
+        'account_name', OLD.account_name,
+        'account_type', OLD.account_type,
+        'liability', OLD.liability,
+        'archived', OLD.archived
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_categories_insert AFTER INSERT ON categories
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('categories', NEW.category_id, 'create',
+    json_object(
+        'category_id', NEW.category_id,
+        'name', NEW.name,
+        'description', NEW.description
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_categories_update AFTER UPDATE ON categories
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('categories', NEW.category_id, 'update',
+    json_object(
+        'category_id', OLD.category_id,
+        'name', OLD.name,
+        'description', OLD.description
+    ),
+    json_object(
+        'category_id', NEW.category_id,
+        'name', NEW.name,
+        'description', NEW.description
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_categories_delete AFTER DELETE ON categories
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('categories', OLD.category_id, 'delete',
+    json_object(
+        'category_id', OLD.category_id,
+        'name', OLD.name,
+        'description', OLD.description
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_insert AFTER INSERT ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('rules', NEW.rule_id, 'create',
+    json_object(
+        'rule_id', NEW.rule_id,
+        'regex', NEW.regex,
+        'category', NEW.category
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_update AFTER UPDATE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('rules', NEW.rule_id, 'update',
+    json_object(
+        'rule_id', OLD.rule_id,
+        'regex', OLD.regex,
+        'category', OLD.category
+    ),
+    json_object(
+        'rule_id', NEW.rule_id,
+        'regex', NEW.regex,
+        'category', NEW.category
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_delete AFTER DELETE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('rules', OLD.rule_id, 'delete',
+    json_object(
+        'rule_id', OLD.rule_id,
+        'regex', OLD.regex,
+        'category', OLD.category
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_insert AFTER INSERT ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'create',
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'tx_date', NEW.tx_date,
+        'category', NEW.category,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled
+    ));
+END;
+
+-- Running balances and the order within a day are derived data, and are rewritten for
+-- many rows at once, so only changes to the other columns are recorded.
+CREATE TRIGGER IF NOT EXISTS audit_transactions_update AFTER UPDATE ON transactions
+WHEN OLD.account IS NOT NEW.account
+    OR OLD.description IS NOT NEW.description
+    OR OLD.tx_date IS NOT NEW.tx_date
+    OR OLD.category IS NOT NEW.category
+    OR OLD.amount IS NOT NEW.amount
+    OR OLD.status IS NOT NEW.status
+    OR OLD.scheduled IS NOT NEW.scheduled
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'update',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'tx_date', OLD.tx_date,
+        'category', OLD.category,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled
+    ),
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'tx_date', NEW.tx_date,
+        'category', NEW.category,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_delete AFTER DELETE ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('transactions', OLD.transaction_id, 'delete',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'tx_date', OLD.tx_date,
+        'category', OLD.category,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled
+    ));
+END;
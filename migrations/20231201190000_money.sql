@@ -0,0 +1,20 @@
+-- Amounts are read as 64-bit integers of cents, which cannot be NULL nor hold decimals.
+UPDATE transactions SET amount=CAST(ROUND(COALESCE(amount, 0)) AS INTEGER)
+WHERE amount IS NULL OR typeof(amount) != 'integer';
+
+UPDATE scheduled_transactions SET amount=CAST(ROUND(COALESCE(amount, 0)) AS INTEGER)
+WHERE amount IS NULL OR typeof(amount) != 'integer';
+
+UPDATE transactions SET accumulated=calc.acc FROM (
+    SELECT transaction_id, (
+        SUM(amount) OVER (
+            PARTITION BY account
+            ORDER BY tx_date, tx_order, transaction_id
+            ROWS BETWEEN
+            UNBOUNDED PRECEDING
+            AND CURRENT ROW
+        )
+    ) acc
+    FROM transactions
+) AS calc
+WHERE transactions.transaction_id=calc.transaction_id;
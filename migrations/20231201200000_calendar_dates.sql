@@ -0,0 +1,111 @@
+-- The conversion below rewrites every transaction, which is not a change worth recording.
+DROP TRIGGER IF EXISTS audit_transactions_insert;
+DROP TRIGGER IF EXISTS audit_transactions_update;
+DROP TRIGGER IF EXISTS audit_transactions_delete;
+
+ALTER TABLE transactions ADD COLUMN value_date DATE;
+ALTER TABLE transactions ADD COLUMN tx_timestamp DATETIME;
+
+-- tx_date keeps the booking day only, the original value is kept as the timestamp.
+UPDATE transactions SET tx_timestamp=tx_date, tx_date=date(tx_date);
+
+-- Transactions of a day used to be ordered by their time first.
+UPDATE transactions SET tx_order=calc.tx_order FROM (
+    SELECT transaction_id, (
+        ROW_NUMBER() OVER (
+            PARTITION BY account, tx_date
+            ORDER BY tx_timestamp, tx_order, transaction_id
+        )
+    ) tx_order
+    FROM transactions
+) AS calc
+WHERE transactions.transaction_id=calc.transaction_id;
+
+UPDATE transactions SET accumulated=calc.acc FROM (
+    SELECT transaction_id, (
+        SUM(amount) OVER (
+            PARTITION BY account
+            ORDER BY tx_date, tx_order, transaction_id
+            ROWS BETWEEN
+            UNBOUNDED PRECEDING
+            AND CURRENT ROW
+        )
+    ) acc
+    FROM transactions
+) AS calc
+WHERE transactions.transaction_id=calc.transaction_id;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_insert AFTER INSERT ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'create',
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'tx_date', NEW.tx_date,
+        'value_date', NEW.value_date,
+        'tx_timestamp', NEW.tx_timestamp,
+        'category', NEW.category,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_update AFTER UPDATE ON transactions
+WHEN OLD.account IS NOT NEW.account
+    OR OLD.description IS NOT NEW.description
+    OR OLD.tx_date IS NOT NEW.tx_date
+    OR OLD.value_date IS NOT NEW.value_date
+    OR OLD.tx_timestamp IS NOT NEW.tx_timestamp
+    OR OLD.category IS NOT NEW.category
+    OR OLD.amount IS NOT NEW.amount
+    OR OLD.status IS NOT NEW.status
+    OR OLD.scheduled IS NOT NEW.scheduled
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'update',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'tx_date', OLD.tx_date,
+        'value_date', OLD.value_date,
+        'tx_timestamp', OLD.tx_timestamp,
+        'category', OLD.category,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled
+    ),
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'tx_date', NEW.tx_date,
+        'value_date', NEW.value_date,
+        'tx_timestamp', NEW.tx_timestamp,
+        'category', NEW.category,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_delete AFTER DELETE ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('transactions', OLD.transaction_id, 'delete',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'tx_date', OLD.tx_date,
+        'value_date', OLD.value_date,
+        'tx_timestamp', OLD.tx_timestamp,
+        'category', OLD.category,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled
+    ));
+END;
@@ -0,0 +1,54 @@
+-- Rules are evaluated by ascending priority, the first one that matches wins.
+ALTER TABLE rules ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+
+UPDATE rules SET priority=calc.priority FROM (
+    SELECT rule_id, ROW_NUMBER() OVER (ORDER BY rule_id) AS priority
+    FROM rules
+) AS calc
+WHERE rules.rule_id=calc.rule_id;
+
+DROP TRIGGER IF EXISTS audit_rules_insert;
+DROP TRIGGER IF EXISTS audit_rules_update;
+DROP TRIGGER IF EXISTS audit_rules_delete;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_insert AFTER INSERT ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('rules', NEW.rule_id, 'create',
+    json_object(
+        'rule_id', NEW.rule_id,
+        'regex', NEW.regex,
+        'category', NEW.category,
+        'priority', NEW.priority
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_update AFTER UPDATE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('rules', NEW.rule_id, 'update',
+    json_object(
+        'rule_id', OLD.rule_id,
+        'regex', OLD.regex,
+        'category', OLD.category,
+        'priority', OLD.priority
+    ),
+    json_object(
+        'rule_id', NEW.rule_id,
+        'regex', NEW.regex,
+        'category', NEW.category,
+        'priority', NEW.priority
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_delete AFTER DELETE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('rules', OLD.rule_id, 'delete',
+    json_object(
+        'rule_id', OLD.rule_id,
+        'regex', OLD.regex,
+        'category', OLD.category,
+        'priority', OLD.priority
+    ));
+END;
@@ -0,0 +1,149 @@
+-- Name of the counterpart, when the statement reports it apart from the description.
+ALTER TABLE transactions ADD COLUMN payee TEXT;
+
+-- Whether every condition of the rule has to match, or any of them.
+ALTER TABLE rules ADD COLUMN match_all BOOLEAN NOT NULL DEFAULT 1;
+
+CREATE TABLE IF NOT EXISTS rule_conditions(
+    condition_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    rule INTEGER NOT NULL,
+    field TEXT NOT NULL,
+    operator TEXT NOT NULL,
+    value TEXT NOT NULL,
+    FOREIGN KEY (rule) REFERENCES rules(rule_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_rule_conditions_rule ON rule_conditions(rule);
+
+DROP TRIGGER IF EXISTS audit_transactions_insert;
+DROP TRIGGER IF EXISTS audit_transactions_update;
+DROP TRIGGER IF EXISTS audit_transactions_delete;
+DROP TRIGGER IF EXISTS audit_rules_insert;
+DROP TRIGGER IF EXISTS audit_rules_update;
+DROP TRIGGER IF EXISTS audit_rules_delete;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_insert AFTER INSERT ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'create',
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'payee', NEW.payee,
+        'tx_date', NEW.tx_date,
+        'value_date', NEW.value_date,
+        'tx_timestamp', NEW.tx_timestamp,
+        'category', NEW.category,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_update AFTER UPDATE ON transactions
+WHEN OLD.account IS NOT NEW.account
+    OR OLD.description IS NOT NEW.description
+    OR OLD.payee IS NOT NEW.payee
+    OR OLD.tx_date IS NOT NEW.tx_date
+    OR OLD.value_date IS NOT NEW.value_date
+    OR OLD.tx_timestamp IS NOT NEW.tx_timestamp
+    OR OLD.category IS NOT NEW.category
+    OR OLD.amount IS NOT NEW.amount
+    OR OLD.status IS NOT NEW.status
+    OR OLD.scheduled IS NOT NEW.scheduled
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'update',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'payee', OLD.payee,
+        'tx_date', OLD.tx_date,
+        'value_date', OLD.value_date,
+        'tx_timestamp', OLD.tx_timestamp,
+        'category', OLD.category,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled
+    ),
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'payee', NEW.payee,
+        'tx_date', NEW.tx_date,
+        'value_date', NEW.value_date,
+        'tx_timestamp', NEW.tx_timestamp,
+        'category', NEW.category,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_delete AFTER DELETE ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('transactions', OLD.transaction_id, 'delete',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'payee', OLD.payee,
+        'tx_date', OLD.tx_date,
+        'value_date', OLD.value_date,
+        'tx_timestamp', OLD.tx_timestamp,
+        'category', OLD.category,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_insert AFTER INSERT ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('rules', NEW.rule_id, 'create',
+    json_object(
+        'rule_id', NEW.rule_id,
+        'regex', NEW.regex,
+        'category', NEW.category,
+        'priority', NEW.priority,
+        'match_all', NEW.match_all
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_update AFTER UPDATE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('rules', NEW.rule_id, 'update',
+    json_object(
+        'rule_id', OLD.rule_id,
+        'regex', OLD.regex,
+        'category', OLD.category,
+        'priority', OLD.priority,
+        'match_all', OLD.match_all
+    ),
+    json_object(
+        'rule_id', NEW.rule_id,
+        'regex', NEW.regex,
+        'category', NEW.category,
+        'priority', NEW.priority,
+        'match_all', NEW.match_all
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_delete AFTER DELETE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('rules', OLD.rule_id, 'delete',
+    json_object(
+        'rule_id', OLD.rule_id,
+        'regex', OLD.regex,
+        'category', OLD.category,
+        'priority', OLD.priority,
+        'match_all', OLD.match_all
+    ));
+END;
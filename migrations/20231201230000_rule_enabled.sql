@@ -0,0 +1,55 @@
+ALTER TABLE rules ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT 1;
+
+DROP TRIGGER IF EXISTS audit_rules_insert;
+DROP TRIGGER IF EXISTS audit_rules_update;
+DROP TRIGGER IF EXISTS audit_rules_delete;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_insert AFTER INSERT ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('rules', NEW.rule_id, 'create',
+    json_object(
+        'rule_id', NEW.rule_id,
+        'regex', NEW.regex,
+        'category', NEW.category,
+        'priority', NEW.priority,
+        'match_all', NEW.match_all,
+        'enabled', NEW.enabled
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_update AFTER UPDATE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('rules', NEW.rule_id, 'update',
+    json_object(
+        'rule_id', OLD.rule_id,
+        'regex', OLD.regex,
+        'category', OLD.category,
+        'priority', OLD.priority,
+        'match_all', OLD.match_all,
+        'enabled', OLD.enabled
+    ),
+    json_object(
+        'rule_id', NEW.rule_id,
+        'regex', NEW.regex,
+        'category', NEW.category,
+        'priority', NEW.priority,
+        'match_all', NEW.match_all,
+        'enabled', NEW.enabled
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_delete AFTER DELETE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('rules', OLD.rule_id, 'delete',
+    json_object(
+        'rule_id', OLD.rule_id,
+        'regex', OLD.regex,
+        'category', OLD.category,
+        'priority', OLD.priority,
+        'match_all', OLD.match_all,
+        'enabled', OLD.enabled
+    ));
+END;
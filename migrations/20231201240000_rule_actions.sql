@@ -0,0 +1,118 @@
+-- Account on the other side, when the transaction moves money between own accounts.
+ALTER TABLE transactions ADD COLUMN transfer_account INTEGER REFERENCES accounts(account_id) ON DELETE SET NULL;
+
+-- Set by rules for transactions that should be checked by hand.
+ALTER TABLE transactions ADD COLUMN needs_review BOOLEAN NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS transaction_tags(
+    transaction_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (transaction_id, tag),
+    FOREIGN KEY (transaction_id) REFERENCES transactions(transaction_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_transaction_tags_tag ON transaction_tags(tag);
+
+CREATE TABLE IF NOT EXISTS rule_actions(
+    action_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    rule INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    value TEXT NOT NULL DEFAULT '',
+    FOREIGN KEY (rule) REFERENCES rules(rule_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_rule_actions_rule ON rule_actions(rule);
+
+DROP TRIGGER IF EXISTS audit_transactions_insert;
+DROP TRIGGER IF EXISTS audit_transactions_update;
+DROP TRIGGER IF EXISTS audit_transactions_delete;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_insert AFTER INSERT ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'create',
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'payee', NEW.payee,
+        'tx_date', NEW.tx_date,
+        'value_date', NEW.value_date,
+        'tx_timestamp', NEW.tx_timestamp,
+        'category', NEW.category,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled,
+        'transfer_account', NEW.transfer_account,
+        'needs_review', NEW.needs_review
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_update AFTER UPDATE ON transactions
+WHEN OLD.account IS NOT NEW.account
+    OR OLD.description IS NOT NEW.description
+    OR OLD.payee IS NOT NEW.payee
+    OR OLD.tx_date IS NOT NEW.tx_date
+    OR OLD.value_date IS NOT NEW.value_date
+    OR OLD.tx_timestamp IS NOT NEW.tx_timestamp
+    OR OLD.category IS NOT NEW.category
+    OR OLD.amount IS NOT NEW.amount
+    OR OLD.status IS NOT NEW.status
+    OR OLD.scheduled IS NOT NEW.scheduled
+    OR OLD.transfer_account IS NOT NEW.transfer_account
+    OR OLD.needs_review IS NOT NEW.needs_review
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'update',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'payee', OLD.payee,
+        'tx_date', OLD.tx_date,
+        'value_date', OLD.value_date,
+        'tx_timestamp', OLD.tx_timestamp,
+        'category', OLD.category,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled,
+        'transfer_account', OLD.transfer_account,
+        'needs_review', OLD.needs_review
+    ),
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'payee', NEW.payee,
+        'tx_date', NEW.tx_date,
+        'value_date', NEW.value_date,
+        'tx_timestamp', NEW.tx_timestamp,
+        'category', NEW.category,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled,
+        'transfer_account', NEW.transfer_account,
+        'needs_review', NEW.needs_review
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_delete AFTER DELETE ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('transactions', OLD.transaction_id, 'delete',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'payee', OLD.payee,
+        'tx_date', OLD.tx_date,
+        'value_date', OLD.value_date,
+        'tx_timestamp', OLD.tx_timestamp,
+        'category', OLD.category,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled,
+        'transfer_account', OLD.transfer_account,
+        'needs_review', OLD.needs_review
+    ));
+END;
@@ -0,0 +1,112 @@
+-- Whether the category was set by hand ('manual') or by a rule ('rule'), and which rule.
+ALTER TABLE transactions ADD COLUMN category_source TEXT;
+ALTER TABLE transactions ADD COLUMN category_rule INTEGER REFERENCES rules(rule_id) ON DELETE SET NULL;
+
+-- There is no telling how existing categories were set, so they are kept as manual ones
+-- and never overwritten by the rules. This runs before the triggers are recreated, as it
+-- is not a change worth recording.
+DROP TRIGGER IF EXISTS audit_transactions_update;
+UPDATE transactions SET category_source='manual' WHERE category IS NOT NULL;
+
+DROP TRIGGER IF EXISTS audit_transactions_insert;
+DROP TRIGGER IF EXISTS audit_transactions_delete;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_insert AFTER INSERT ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'create',
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'payee', NEW.payee,
+        'tx_date', NEW.tx_date,
+        'value_date', NEW.value_date,
+        'tx_timestamp', NEW.tx_timestamp,
+        'category', NEW.category,
+        'category_source', NEW.category_source,
+        'category_rule', NEW.category_rule,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled,
+        'transfer_account', NEW.transfer_account,
+        'needs_review', NEW.needs_review
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_update AFTER UPDATE ON transactions
+WHEN OLD.account IS NOT NEW.account
+    OR OLD.description IS NOT NEW.description
+    OR OLD.payee IS NOT NEW.payee
+    OR OLD.tx_date IS NOT NEW.tx_date
+    OR OLD.value_date IS NOT NEW.value_date
+    OR OLD.tx_timestamp IS NOT NEW.tx_timestamp
+    OR OLD.category IS NOT NEW.category
+    OR OLD.category_source IS NOT NEW.category_source
+    OR OLD.category_rule IS NOT NEW.category_rule
+    OR OLD.amount IS NOT NEW.amount
+    OR OLD.status IS NOT NEW.status
+    OR OLD.scheduled IS NOT NEW.scheduled
+    OR OLD.transfer_account IS NOT NEW.transfer_account
+    OR OLD.needs_review IS NOT NEW.needs_review
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('transactions', NEW.transaction_id, 'update',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'payee', OLD.payee,
+        'tx_date', OLD.tx_date,
+        'value_date', OLD.value_date,
+        'tx_timestamp', OLD.tx_timestamp,
+        'category', OLD.category,
+        'category_source', OLD.category_source,
+        'category_rule', OLD.category_rule,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled,
+        'transfer_account', OLD.transfer_account,
+        'needs_review', OLD.needs_review
+    ),
+    json_object(
+        'transaction_id', NEW.transaction_id,
+        'account', NEW.account,
+        'description', NEW.description,
+        'payee', NEW.payee,
+        'tx_date', NEW.tx_date,
+        'value_date', NEW.value_date,
+        'tx_timestamp', NEW.tx_timestamp,
+        'category', NEW.category,
+        'category_source', NEW.category_source,
+        'category_rule', NEW.category_rule,
+        'amount', NEW.amount,
+        'status', NEW.status,
+        'scheduled', NEW.scheduled,
+        'transfer_account', NEW.transfer_account,
+        'needs_review', NEW.needs_review
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_delete AFTER DELETE ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('transactions', OLD.transaction_id, 'delete',
+    json_object(
+        'transaction_id', OLD.transaction_id,
+        'account', OLD.account,
+        'description', OLD.description,
+        'payee', OLD.payee,
+        'tx_date', OLD.tx_date,
+        'value_date', OLD.value_date,
+        'tx_timestamp', OLD.tx_timestamp,
+        'category', OLD.category,
+        'category_source', OLD.category_source,
+        'category_rule', OLD.category_rule,
+        'amount', OLD.amount,
+        'status', OLD.status,
+        'scheduled', OLD.scheduled,
+        'transfer_account', OLD.transfer_account,
+        'needs_review', OLD.needs_review
+    ));
+END;
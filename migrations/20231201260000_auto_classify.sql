@@ -0,0 +1,56 @@
+-- Whether new transactions of the account go through the classification rules.
+ALTER TABLE accounts ADD COLUMN auto_classify BOOLEAN NOT NULL DEFAULT 1;
+
+DROP TRIGGER IF EXISTS audit_accounts_insert;
+DROP TRIGGER IF EXISTS audit_accounts_update;
+DROP TRIGGER IF EXISTS audit_accounts_delete;
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_insert AFTER INSERT ON accounts
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, new_value)
+    VALUES ('accounts', NEW.account_id, 'create',
+    json_object(
+        'account_id', NEW.account_id,
+        'account_name', NEW.account_name,
+        'account_type', NEW.account_type,
+        'liability', NEW.liability,
+        'archived', NEW.archived,
+        'auto_classify', NEW.auto_classify
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_update AFTER UPDATE ON accounts
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value, new_value)
+    VALUES ('accounts', NEW.account_id, 'update',
+    json_object(
+        'account_id', OLD.account_id,
+        'account_name', OLD.account_name,
+        'account_type', OLD.account_type,
+        'liability', OLD.liability,
+        'archived', OLD.archived,
+        'auto_classify', OLD.auto_classify
+    ),
+    json_object(
+        'account_id', NEW.account_id,
+        'account_name', NEW.account_name,
+        'account_type', NEW.account_type,
+        'liability', NEW.liability,
+        'archived', NEW.archived,
+        'auto_classify', NEW.auto_classify
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_delete AFTER DELETE ON accounts
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, old_value)
+    VALUES ('accounts', OLD.account_id, 'delete',
+    json_object(
+        'account_id', OLD.account_id,
+        'account_name', OLD.account_name,
+        'account_type', OLD.account_type,
+        'liability', OLD.liability,
+        'archived', OLD.archived,
+        'auto_classify', OLD.auto_classify
+    ));
+END;
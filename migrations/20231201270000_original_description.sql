@@ -0,0 +1,3 @@
+-- Description as imported, kept when a rule renames the transaction so that the rules
+-- can still be applied again to it.
+ALTER TABLE transactions ADD COLUMN original_description TEXT;
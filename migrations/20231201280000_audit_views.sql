@@ -0,0 +1,300 @@
+-- User doing the changes, recorded by the audit triggers. It is set at the start of the
+-- database transaction that makes the changes and cleared before it commits, and SQLite
+-- runs one write transaction at a time, so no other change ever sees it.
+CREATE TABLE IF NOT EXISTS audit_context(
+    context_id INTEGER PRIMARY KEY CHECK (context_id = 1),
+    user_id INTEGER
+);
+
+INSERT OR IGNORE INTO audit_context(context_id) VALUES (1);
+
+-- The JSON stored for a row is built by one view per table, so adding a column only
+-- needs the view to be recreated. Updates log the row as it is before and after, and
+-- drop the entry when nothing in it changed: running balances and the order within a
+-- day are left out of the views, and are rewritten for many rows at once.
+
+DROP TRIGGER IF EXISTS audit_accounts_insert;
+DROP TRIGGER IF EXISTS audit_accounts_update;
+DROP TRIGGER IF EXISTS audit_accounts_delete;
+
+DROP TRIGGER IF EXISTS audit_categories_insert;
+DROP TRIGGER IF EXISTS audit_categories_update;
+DROP TRIGGER IF EXISTS audit_categories_delete;
+
+DROP TRIGGER IF EXISTS audit_rules_insert;
+DROP TRIGGER IF EXISTS audit_rules_update;
+DROP TRIGGER IF EXISTS audit_rules_delete;
+
+DROP TRIGGER IF EXISTS audit_rule_conditions_insert;
+DROP TRIGGER IF EXISTS audit_rule_conditions_update;
+DROP TRIGGER IF EXISTS audit_rule_conditions_delete;
+
+DROP TRIGGER IF EXISTS audit_rule_actions_insert;
+DROP TRIGGER IF EXISTS audit_rule_actions_update;
+DROP TRIGGER IF EXISTS audit_rule_actions_delete;
+
+DROP TRIGGER IF EXISTS audit_transactions_insert;
+DROP TRIGGER IF EXISTS audit_transactions_update;
+DROP TRIGGER IF EXISTS audit_transactions_delete;
+
+CREATE VIEW IF NOT EXISTS audit_accounts_row AS
+SELECT account_id AS row_id, json_object(
+        'account_id', account_id,
+        'account_name', account_name,
+        'account_type', account_type,
+        'liability', liability,
+        'archived', archived,
+        'auto_classify', auto_classify
+    ) AS row_value
+FROM accounts;
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_insert AFTER INSERT ON accounts
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, new_value)
+    SELECT 'accounts', row_id, 'create', (SELECT user_id FROM audit_context), row_value
+    FROM audit_accounts_row WHERE row_id = NEW.account_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_before_update BEFORE UPDATE ON accounts
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'accounts', row_id, 'update', (SELECT user_id FROM audit_context), row_value
+    FROM audit_accounts_row WHERE row_id = OLD.account_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_update AFTER UPDATE ON accounts
+BEGIN
+    UPDATE audit_log
+    SET row_id = NEW.account_id,
+        new_value = (SELECT row_value FROM audit_accounts_row WHERE row_id = NEW.account_id)
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'accounts' AND row_id = OLD.account_id);
+    DELETE FROM audit_log
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'accounts' AND row_id = NEW.account_id)
+        AND old_value IS new_value;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_accounts_delete BEFORE DELETE ON accounts
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'accounts', row_id, 'delete', (SELECT user_id FROM audit_context), row_value
+    FROM audit_accounts_row WHERE row_id = OLD.account_id;
+END;
+
+CREATE VIEW IF NOT EXISTS audit_categories_row AS
+SELECT category_id AS row_id, json_object(
+        'category_id', category_id,
+        'name', name,
+        'description', description
+    ) AS row_value
+FROM categories;
+
+CREATE TRIGGER IF NOT EXISTS audit_categories_insert AFTER INSERT ON categories
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, new_value)
+    SELECT 'categories', row_id, 'create', (SELECT user_id FROM audit_context), row_value
+    FROM audit_categories_row WHERE row_id = NEW.category_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_categories_before_update BEFORE UPDATE ON categories
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'categories', row_id, 'update', (SELECT user_id FROM audit_context), row_value
+    FROM audit_categories_row WHERE row_id = OLD.category_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_categories_update AFTER UPDATE ON categories
+BEGIN
+    UPDATE audit_log
+    SET row_id = NEW.category_id,
+        new_value = (SELECT row_value FROM audit_categories_row WHERE row_id = NEW.category_id)
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'categories' AND row_id = OLD.category_id);
+    DELETE FROM audit_log
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'categories' AND row_id = NEW.category_id)
+        AND old_value IS new_value;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_categories_delete BEFORE DELETE ON categories
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'categories', row_id, 'delete', (SELECT user_id FROM audit_context), row_value
+    FROM audit_categories_row WHERE row_id = OLD.category_id;
+END;
+
+CREATE VIEW IF NOT EXISTS audit_rules_row AS
+SELECT rule_id AS row_id, json_object(
+        'rule_id', rule_id,
+        'regex', regex,
+        'category', category,
+        'priority', priority,
+        'match_all', match_all,
+        'enabled', enabled
+    ) AS row_value
+FROM rules;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_insert AFTER INSERT ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, new_value)
+    SELECT 'rules', row_id, 'create', (SELECT user_id FROM audit_context), row_value
+    FROM audit_rules_row WHERE row_id = NEW.rule_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_before_update BEFORE UPDATE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'rules', row_id, 'update', (SELECT user_id FROM audit_context), row_value
+    FROM audit_rules_row WHERE row_id = OLD.rule_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_update AFTER UPDATE ON rules
+BEGIN
+    UPDATE audit_log
+    SET row_id = NEW.rule_id,
+        new_value = (SELECT row_value FROM audit_rules_row WHERE row_id = NEW.rule_id)
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'rules' AND row_id = OLD.rule_id);
+    DELETE FROM audit_log
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'rules' AND row_id = NEW.rule_id)
+        AND old_value IS new_value;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rules_delete BEFORE DELETE ON rules
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'rules', row_id, 'delete', (SELECT user_id FROM audit_context), row_value
+    FROM audit_rules_row WHERE row_id = OLD.rule_id;
+END;
+
+CREATE VIEW IF NOT EXISTS audit_rule_conditions_row AS
+SELECT condition_id AS row_id, json_object(
+        'condition_id', condition_id,
+        'rule', rule,
+        'field', field,
+        'operator', operator,
+        'value', value
+    ) AS row_value
+FROM rule_conditions;
+
+CREATE TRIGGER IF NOT EXISTS audit_rule_conditions_insert AFTER INSERT ON rule_conditions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, new_value)
+    SELECT 'rule_conditions', row_id, 'create', (SELECT user_id FROM audit_context), row_value
+    FROM audit_rule_conditions_row WHERE row_id = NEW.condition_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rule_conditions_before_update BEFORE UPDATE ON rule_conditions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'rule_conditions', row_id, 'update', (SELECT user_id FROM audit_context), row_value
+    FROM audit_rule_conditions_row WHERE row_id = OLD.condition_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rule_conditions_update AFTER UPDATE ON rule_conditions
+BEGIN
+    UPDATE audit_log
+    SET row_id = NEW.condition_id,
+        new_value = (SELECT row_value FROM audit_rule_conditions_row WHERE row_id = NEW.condition_id)
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'rule_conditions' AND row_id = OLD.condition_id);
+    DELETE FROM audit_log
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'rule_conditions' AND row_id = NEW.condition_id)
+        AND old_value IS new_value;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rule_conditions_delete BEFORE DELETE ON rule_conditions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'rule_conditions', row_id, 'delete', (SELECT user_id FROM audit_context), row_value
+    FROM audit_rule_conditions_row WHERE row_id = OLD.condition_id;
+END;
+
+CREATE VIEW IF NOT EXISTS audit_rule_actions_row AS
+SELECT action_id AS row_id, json_object(
+        'action_id', action_id,
+        'rule', rule,
+        'action', action,
+        'value', value
+    ) AS row_value
+FROM rule_actions;
+
+CREATE TRIGGER IF NOT EXISTS audit_rule_actions_insert AFTER INSERT ON rule_actions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, new_value)
+    SELECT 'rule_actions', row_id, 'create', (SELECT user_id FROM audit_context), row_value
+    FROM audit_rule_actions_row WHERE row_id = NEW.action_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rule_actions_before_update BEFORE UPDATE ON rule_actions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'rule_actions', row_id, 'update', (SELECT user_id FROM audit_context), row_value
+    FROM audit_rule_actions_row WHERE row_id = OLD.action_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rule_actions_update AFTER UPDATE ON rule_actions
+BEGIN
+    UPDATE audit_log
+    SET row_id = NEW.action_id,
+        new_value = (SELECT row_value FROM audit_rule_actions_row WHERE row_id = NEW.action_id)
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'rule_actions' AND row_id = OLD.action_id);
+    DELETE FROM audit_log
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'rule_actions' AND row_id = NEW.action_id)
+        AND old_value IS new_value;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_rule_actions_delete BEFORE DELETE ON rule_actions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'rule_actions', row_id, 'delete', (SELECT user_id FROM audit_context), row_value
+    FROM audit_rule_actions_row WHERE row_id = OLD.action_id;
+END;
+
+CREATE VIEW IF NOT EXISTS audit_transactions_row AS
+SELECT transaction_id AS row_id, json_object(
+        'transaction_id', transaction_id,
+        'account', account,
+        'description', description,
+        'original_description', original_description,
+        'payee', payee,
+        'tx_date', tx_date,
+        'value_date', value_date,
+        'tx_timestamp', tx_timestamp,
+        'category', category,
+        'category_source', category_source,
+        'category_rule', category_rule,
+        'amount', amount,
+        'status', status,
+        'scheduled', scheduled,
+        'transfer_account', transfer_account,
+        'needs_review', needs_review
+    ) AS row_value
+FROM transactions;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_insert AFTER INSERT ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, new_value)
+    SELECT 'transactions', row_id, 'create', (SELECT user_id FROM audit_context), row_value
+    FROM audit_transactions_row WHERE row_id = NEW.transaction_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_before_update BEFORE UPDATE ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'transactions', row_id, 'update', (SELECT user_id FROM audit_context), row_value
+    FROM audit_transactions_row WHERE row_id = OLD.transaction_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_update AFTER UPDATE ON transactions
+BEGIN
+    UPDATE audit_log
+    SET row_id = NEW.transaction_id,
+        new_value = (SELECT row_value FROM audit_transactions_row WHERE row_id = NEW.transaction_id)
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'transactions' AND row_id = OLD.transaction_id);
+    DELETE FROM audit_log
+    WHERE audit_id = (SELECT MAX(audit_id) FROM audit_log WHERE table_name = 'transactions' AND row_id = NEW.transaction_id)
+        AND old_value IS new_value;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_transactions_delete BEFORE DELETE ON transactions
+BEGIN
+    INSERT INTO audit_log(table_name, row_id, action, user_id, old_value)
+    SELECT 'transactions', row_id, 'delete', (SELECT user_id FROM audit_context), row_value
+    FROM audit_transactions_row WHERE row_id = OLD.transaction_id;
+END;
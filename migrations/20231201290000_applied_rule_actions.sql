@@ -0,0 +1,13 @@
+-- Rule actions applied to each transaction, with the value they replaced, so that they
+-- are undone when the transaction stops matching the rule. The action is not a foreign key:
+-- actions removed from a rule are undone as well the next time the rules are applied.
+-- `previous` has no type, so the replaced value keeps its own.
+CREATE TABLE IF NOT EXISTS transaction_rule_actions(
+    transaction_id INTEGER NOT NULL,
+    action_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    value TEXT NOT NULL,
+    previous,
+    PRIMARY KEY (transaction_id, action_id),
+    FOREIGN KEY (transaction_id) REFERENCES transactions(transaction_id) ON DELETE CASCADE
+);
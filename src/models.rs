@@ -1,5 +1,15 @@
 pub mod account;
+pub mod audit;
+pub mod budgets;
 pub mod categories;
+pub mod forecast;
+pub mod goals;
+pub mod investments;
+pub mod loans;
+pub mod money;
+pub mod rule_engine;
 pub mod rules;
+pub mod scheduled;
+pub mod subscriptions;
 pub mod transaction;
 pub mod users;
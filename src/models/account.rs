@@ -1,12 +1,70 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, Result, SqlitePool};
+use sqlx::{Acquire, Executor, FromRow, Result, Sqlite, SqlitePool};
 
-use super::{rules::Rule, transaction::Transaction};
+use super::{
+    money::Money,
+    rule_engine::RuleEngine,
+    rules::Rule,
+    transaction::{Transaction, TransactionStatus},
+};
+
+/// Transactions the rules are applied to.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecategorizeMode {
+    /// Only the ones without a category.
+    #[default]
+    Uncategorized,
+    /// Also the ones categorized by a rule, which are updated to the current rules.
+    /// Categories set by hand are kept. Actions added to a rule since are applied, and
+    /// those of a rule the transaction no longer matches are undone.
+    Reapply,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AccountType {
+    #[default]
+    Checking,
+    Savings,
+    CreditCard,
+    Cash,
+    Loan,
+    Investment,
+}
+
+impl AccountType {
+    /// Whether accounts of this type hold money owed rather than money owned.
+    pub fn default_liability(&self) -> bool {
+        matches!(self, AccountType::CreditCard | AccountType::Loan)
+    }
+}
 
 #[derive(FromRow, Serialize, Deserialize, Debug)]
 pub struct Account {
     account_id: i32,
     account_name: String,
+    account_type: AccountType,
+    liability: bool,
+    archived: bool,
+    /// Whether new transactions go through the classification rules.
+    auto_classify: bool,
+}
+
+/// Balance including every transaction (`working`) and excluding pending ones (`cleared`).
+#[derive(Serialize, Debug, Default)]
+pub struct AccountBalances {
+    pub cleared: Money,
+    pub working: Money,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct NetWorth {
+    pub assets: Money,
+    pub liabilities: Money,
+    pub total: Money,
 }
 
 impl Account {
@@ -18,6 +76,74 @@ impl Account {
         self.account_name.as_str()
     }
 
+    pub fn get_account_type(&self) -> AccountType {
+        self.account_type
+    }
+
+    pub fn is_liability(&self) -> bool {
+        self.liability
+    }
+
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
+    pub async fn set_archived(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        archived: bool,
+    ) -> Result<()> {
+        sqlx::query("UPDATE accounts SET archived=? WHERE account_id=?")
+            .bind(archived)
+            .bind(self.account_id)
+            .execute(conn)
+            .await?;
+        self.archived = archived;
+        Ok(())
+    }
+
+    pub fn get_auto_classify(&self) -> bool {
+        self.auto_classify
+    }
+
+    pub async fn set_auto_classify(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        auto_classify: bool,
+    ) -> Result<()> {
+        sqlx::query("UPDATE accounts SET auto_classify=? WHERE account_id=?")
+            .bind(auto_classify)
+            .bind(self.account_id)
+            .execute(conn)
+            .await?;
+        self.auto_classify = auto_classify;
+        Ok(())
+    }
+
+    pub async fn set_account_type(
+        &mut self,
+        pool: &SqlitePool,
+        account_type: AccountType,
+    ) -> Result<()> {
+        sqlx::query("UPDATE accounts SET account_type=? WHERE account_id=?")
+            .bind(account_type)
+            .bind(self.account_id)
+            .execute(pool)
+            .await?;
+        self.account_type = account_type;
+        Ok(())
+    }
+
+    pub async fn set_liability(&mut self, pool: &SqlitePool, liability: bool) -> Result<()> {
+        sqlx::query("UPDATE accounts SET liability=? WHERE account_id=?")
+            .bind(liability)
+            .bind(self.account_id)
+            .execute(pool)
+            .await?;
+        self.liability = liability;
+        Ok(())
+    }
+
     pub async fn set_account_name(&mut self, pool: &SqlitePool, name: &str) -> Result<()> {
         sqlx::query("UPDATE accounts SET account_name=? WHERE account_id=?")
             .bind(name)
@@ -28,26 +154,43 @@ impl Account {
         Ok(())
     }
 
-    pub async fn get_by_id(pool: &SqlitePool, id: i32) -> Result<Self> {
+    pub async fn get_by_id(conn: impl Executor<'_, Database = Sqlite>, id: i32) -> Result<Self> {
         sqlx::query("SELECT * FROM accounts WHERE account_id=?")
             .bind(id)
-            .fetch_one(pool)
+            .fetch_one(conn)
             .await
             .and_then(|r| Account::from_row(&r))
     }
 
-    pub async fn new(pool: &SqlitePool, name: &str) -> Result<Self> {
-        let row = sqlx::query("INSERT INTO accounts(account_name) VALUES (?) RETURNING *")
-            .bind(name)
-            .fetch_one(pool)
-            .await?;
+    pub async fn new(
+        conn: impl Executor<'_, Database = Sqlite>,
+        name: &str,
+        account_type: AccountType,
+        liability: Option<bool>,
+    ) -> Result<Self> {
+        let row = sqlx::query(concat!(
+            "INSERT INTO accounts(account_name, account_type, liability) ",
+            "VALUES (?,?,?) RETURNING *"
+        ))
+        .bind(name)
+        .bind(account_type)
+        .bind(liability.unwrap_or(account_type.default_liability()))
+        .fetch_one(conn)
+        .await?;
         Self::from_row(&row)
     }
 
-    pub async fn list(pool: &SqlitePool) -> Result<Vec<Self>> {
-        let rows = sqlx::query("SELECT * FROM accounts")
-            .fetch_all(pool)
-            .await?;
+    pub async fn list(
+        conn: impl Executor<'_, Database = Sqlite>,
+        include_archived: bool,
+    ) -> Result<Vec<Self>> {
+        let rows = sqlx::query(if include_archived {
+            "SELECT * FROM accounts"
+        } else {
+            "SELECT * FROM accounts WHERE NOT archived"
+        })
+        .fetch_all(conn)
+        .await?;
         let mut res = Vec::new();
         for r in &rows {
             res.push(Account::from_row(r)?)
@@ -55,27 +198,163 @@ impl Account {
         Ok(res)
     }
 
-    pub async fn recategorize_transactions(&self, pool: &SqlitePool) -> Result<()> {
-        let rules = Rule::list(pool).await?;
-        let mut tx_list = Transaction::list_uncategorized(pool, self.account_id).await?;
-        for tx in tx_list.iter_mut() {
-            println!("Checking {}", tx.get_description());
-            if tx.recategorize(pool, &rules).await? {
-                println!(
-                    "Tx {} updated with category {}",
-                    tx.get_id(),
-                    tx.get_category().unwrap_or(0)
-                );
+    /// Removes the account together with all of its transactions.
+    pub async fn delete(self, conn: impl Acquire<'_, Database = Sqlite>) -> Result<()> {
+        let mut db_tx = conn.begin().await?;
+        sqlx::query("DELETE FROM transactions WHERE account=?")
+            .bind(self.account_id)
+            .execute(&mut *db_tx)
+            .await?;
+        sqlx::query("DELETE FROM accounts WHERE account_id=?")
+            .bind(self.account_id)
+            .execute(&mut *db_tx)
+            .await?;
+        db_tx.commit().await
+    }
+
+    /// Running balance after the last transaction of the account, as stored in the ledger.
+    pub async fn get_balance(&self, pool: &SqlitePool) -> Result<Money> {
+        let balance: Option<Money> = sqlx::query_scalar(concat!(
+            "SELECT accumulated FROM transactions WHERE account=? ",
+            "ORDER BY tx_date DESC, tx_order DESC LIMIT 1"
+        ))
+        .bind(self.account_id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(balance.unwrap_or_default())
+    }
+
+    /// Balance at the start of the day `at`, before its transactions.
+    pub async fn get_balance_at(&self, pool: &SqlitePool, at: NaiveDate) -> Result<Money> {
+        let balance: Option<Money> = sqlx::query_scalar(concat!(
+            "SELECT accumulated FROM transactions WHERE account=? AND tx_date < ? ",
+            "ORDER BY tx_date DESC, tx_order DESC LIMIT 1"
+        ))
+        .bind(self.account_id)
+        .bind(at)
+        .fetch_optional(pool)
+        .await?;
+        Ok(balance.unwrap_or_default())
+    }
+
+    pub async fn get_balances(&self, pool: &SqlitePool) -> Result<AccountBalances> {
+        let pending: Option<Money> =
+            sqlx::query_scalar("SELECT SUM(amount) FROM transactions WHERE account=? AND status=?")
+                .bind(self.account_id)
+                .bind(TransactionStatus::Pending)
+                .fetch_one(pool)
+                .await?;
+        let working = self.get_balance(pool).await?;
+        Ok(AccountBalances {
+            cleared: working - pending.unwrap_or_default(),
+            working,
+        })
+    }
+
+    /// Balance as it should be shown to the user. Transactions keep the sign they have
+    /// from the account holder's point of view (a card purchase is negative), so for
+    /// liabilities the amount owed is the opposite of the ledger balance.
+    pub fn display_balance(&self, balance: Money) -> Money {
+        if self.liability {
+            -balance
+        } else {
+            balance
+        }
+    }
+
+    /// Assets and liabilities of the accounts in use. Archived accounts are left out.
+    pub async fn net_worth(pool: &SqlitePool) -> Result<NetWorth> {
+        let mut res = NetWorth::default();
+        for acc in Self::list(pool, false).await? {
+            let balance = acc.display_balance(acc.get_balance(pool).await?);
+            if acc.liability {
+                res.liabilities += balance;
+            } else {
+                res.assets += balance;
             }
         }
+        res.total = res.assets - res.liabilities;
+        Ok(res)
+    }
 
-        Ok(())
+    /// Classifies the transactions in memory, and stores the result of the rules at once.
+    /// Returns the number of transactions updated.
+    pub async fn recategorize_transactions(
+        &self,
+        conn: impl Acquire<'_, Database = Sqlite>,
+        engine: &RuleEngine,
+        mode: RecategorizeMode,
+    ) -> Result<usize> {
+        let mut db_tx = conn.begin().await?;
+        let tx_list = match mode {
+            RecategorizeMode::Uncategorized => {
+                Transaction::list_uncategorized(&mut *db_tx, self.account_id).await?
+            }
+            RecategorizeMode::Reapply => {
+                Transaction::list_rule_managed(&mut *db_tx, self.account_id).await?
+            }
+        };
+
+        let updates: Vec<(i32, Option<&Rule>)> = tx_list
+            .iter()
+            .map(|tx| (tx.get_id(), engine.classify(tx)))
+            .collect();
+
+        let updated = Transaction::apply_rules(&mut *db_tx, &updates).await?;
+        db_tx.commit().await?;
+        Ok(updated)
+    }
+
+    /// Classifies transactions just added to the account, unless the account has automatic
+    /// classification turned off. Transactions that already have a category are left as
+    /// they are. Returns the number of transactions classified.
+    pub async fn classify_new(
+        &self,
+        conn: impl Acquire<'_, Database = Sqlite>,
+        engine: &RuleEngine,
+        txs: &[Transaction],
+    ) -> Result<usize> {
+        if !self.auto_classify {
+            return Ok(0);
+        }
+
+        let matches: Vec<(i32, Option<&Rule>)> = txs
+            .iter()
+            .filter(|tx| tx.get_category().is_none() && !tx.is_locked())
+            .filter_map(|tx| engine.classify(tx).map(|r| (tx.get_id(), Some(r))))
+            .collect();
+        Transaction::apply_rules(conn, &matches).await
+    }
+
+    /// Recategorizes the transactions of every account that is not archived.
+    pub async fn recategorize_all(
+        conn: impl Acquire<'_, Database = Sqlite>,
+        engine: &RuleEngine,
+        mode: RecategorizeMode,
+    ) -> Result<usize> {
+        let mut db_tx = conn.begin().await?;
+        let mut updated = 0;
+        for acc in Self::list(&mut *db_tx, false).await? {
+            updated += acc
+                .recategorize_transactions(&mut *db_tx, engine, mode)
+                .await?;
+        }
+        db_tx.commit().await?;
+        Ok(updated)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Account;
+    use super::{Account, AccountType};
+    use crate::models::{
+        categories::Category,
+        money::Money,
+        rule_engine::RuleEngine,
+        rules::Rule,
+        transaction::{Transaction, TransactionStatus},
+    };
+    use chrono::NaiveDate;
     use sqlx::SqlitePool;
 
     async fn get_db() -> SqlitePool {
@@ -90,7 +369,155 @@ mod tests {
     #[tokio::test]
     async fn create_test() {
         let pool = get_db().await;
-        Account::new(&pool, "account_test").await.unwrap();
+        Account::new(&pool, "account_test", AccountType::Checking, None)
+            .await
+            .unwrap();
         remove_db(pool).await;
     }
+
+    #[tokio::test]
+    async fn liability_test() {
+        let pool = crate::create_db("sqlite://account_liability_test.db")
+            .await
+            .unwrap();
+        let card = Account::new(&pool, "card", AccountType::CreditCard, None)
+            .await
+            .unwrap();
+        assert!(card.is_liability());
+        assert_eq!(
+            card.display_balance(Money::from_cents(-25000)),
+            Money::from_cents(25000)
+        );
+
+        let savings = Account::new(&pool, "savings", AccountType::Savings, Some(true))
+            .await
+            .unwrap();
+        assert!(savings.is_liability());
+
+        pool.close().await;
+        std::fs::remove_file("account_liability_test.db").unwrap();
+    }
+
+    #[tokio::test]
+    async fn archive_delete_test() {
+        let pool = crate::create_db("sqlite://account_archive_test.db")
+            .await
+            .unwrap();
+        let mut acc = Account::new(&pool, "closed", AccountType::Savings, None)
+            .await
+            .unwrap();
+        Transaction::new(
+            &pool,
+            acc.get_id(),
+            "interest",
+            chrono::Utc::now().date_naive(),
+            None,
+            Money::from_cents(10),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            Account::net_worth(&pool).await.unwrap().total,
+            Money::from_cents(10)
+        );
+        acc.set_archived(&pool, true).await.unwrap();
+        assert!(Account::list(&pool, false).await.unwrap().is_empty());
+        assert_eq!(Account::list(&pool, true).await.unwrap().len(), 1);
+        assert_eq!(Account::net_worth(&pool).await.unwrap().total, Money::ZERO);
+
+        let id = acc.get_id();
+        acc.delete(&pool).await.unwrap();
+        assert!(Account::get_by_id(&pool, id).await.is_err());
+        assert!(Transaction::list_by_account(&pool, id, 10, 0, true)
+            .await
+            .unwrap()
+            .is_empty());
+
+        pool.close().await;
+        std::fs::remove_file("account_archive_test.db").unwrap();
+    }
+
+    #[tokio::test]
+    async fn classify_new_test() {
+        let pool = crate::create_db("sqlite://account_classify_new_test.db")
+            .await
+            .unwrap();
+        let mut acc = Account::new(&pool, "classify_new_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        assert!(acc.get_auto_classify());
+        let fuel = Category::new(&pool, "Fuel", "").await.unwrap();
+        let travel = Category::new(&pool, "Travel", "").await.unwrap();
+        Rule::new(&pool, "REPSOL".to_string(), fuel.category_id)
+            .await
+            .unwrap();
+
+        let found = Category::get_by_name(&pool, " travel").await.unwrap();
+        assert_eq!(found.map(|c| c.category_id), Some(travel.category_id));
+        assert!(Category::get_by_name(&pool, "Hotels")
+            .await
+            .unwrap()
+            .is_none());
+
+        let mut created = Vec::new();
+        for category in [None, Some(travel.category_id)] {
+            created.push(
+                Transaction::new(
+                    &pool,
+                    acc.get_id(),
+                    "REPSOL AP-7",
+                    NaiveDate::from_ymd_opt(2023, 12, 1).unwrap(),
+                    category,
+                    Money::from_cents(-6000),
+                    TransactionStatus::Cleared,
+                )
+                .await
+                .unwrap(),
+            );
+        }
+
+        // The category given on insert is kept.
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert_eq!(acc.classify_new(&pool, &engine, &created).await.unwrap(), 1);
+        let categories = [
+            Transaction::get_by_id(&pool, created[0].get_id())
+                .await
+                .unwrap()
+                .get_category(),
+            Transaction::get_by_id(&pool, created[1].get_id())
+                .await
+                .unwrap()
+                .get_category(),
+        ];
+        assert_eq!(
+            categories,
+            [Some(fuel.category_id), Some(travel.category_id)]
+        );
+
+        acc.set_auto_classify(&pool, false).await.unwrap();
+        let mut tx = Transaction::new(
+            &pool,
+            acc.get_id(),
+            "REPSOL AP-7",
+            NaiveDate::from_ymd_opt(2023, 12, 2).unwrap(),
+            None,
+            Money::from_cents(-5500),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            acc.classify_new(&pool, &engine, std::slice::from_ref(&tx))
+                .await
+                .unwrap(),
+            0
+        );
+        tx = Transaction::get_by_id(&pool, tx.get_id()).await.unwrap();
+        assert_eq!(tx.get_category(), None);
+
+        pool.close().await;
+        std::fs::remove_file("account_classify_new_test.db").unwrap();
+    }
 }
@@ -0,0 +1,226 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{FromRow, Result, Sqlite, SqlitePool, Transaction};
+
+/// Starts a database transaction whose changes are recorded as done by `user` in the
+/// audit log. The user is cleared again by [`commit`], so it is never committed, and
+/// dropping the transaction instead rolls it back along with the changes.
+pub async fn begin(pool: &SqlitePool, user: Option<i32>) -> Result<Transaction<'static, Sqlite>> {
+    let mut db_tx = pool.begin().await?;
+    if user.is_some() {
+        sqlx::query("UPDATE audit_context SET user_id=?")
+            .bind(user)
+            .execute(&mut *db_tx)
+            .await?;
+    }
+    Ok(db_tx)
+}
+
+/// Commits a transaction started with [`begin`].
+pub async fn commit(mut db_tx: Transaction<'_, Sqlite>) -> Result<()> {
+    sqlx::query("UPDATE audit_context SET user_id=NULL WHERE user_id IS NOT NULL")
+        .execute(&mut *db_tx)
+        .await?;
+    db_tx.commit().await
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// A row of the audit log. Entries are written by database triggers, so they are only
+/// read from here.
+#[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct AuditEntry {
+    audit_id: i32,
+    table_name: String,
+    row_id: i32,
+    action: AuditAction,
+    old_value: Option<String>,
+    new_value: Option<String>,
+    user_id: Option<i32>,
+    changed_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+fn parse_row(value: &Option<String>) -> Map<String, Value> {
+    match value.as_deref().map(serde_json::from_str::<Value>) {
+        Some(Ok(Value::Object(map))) => map,
+        _ => Map::new(),
+    }
+}
+
+impl AuditEntry {
+    pub async fn list(pool: &SqlitePool, limit: i32, offset: i32) -> Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for r in sqlx::query("SELECT * FROM audit_log ORDER BY audit_id DESC LIMIT ? OFFSET ?")
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await?
+            .iter()
+        {
+            res.push(AuditEntry::from_row(r)?)
+        }
+        Ok(res)
+    }
+
+    /// History of a single row, oldest change first.
+    pub async fn list_for_row(pool: &SqlitePool, table: &str, row_id: i32) -> Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for r in
+            sqlx::query("SELECT * FROM audit_log WHERE table_name=? AND row_id=? ORDER BY audit_id")
+                .bind(table)
+                .bind(row_id)
+                .fetch_all(pool)
+                .await?
+                .iter()
+        {
+            res.push(AuditEntry::from_row(r)?)
+        }
+        Ok(res)
+    }
+
+    pub fn get_action(&self) -> AuditAction {
+        self.action
+    }
+
+    pub fn get_user(&self) -> Option<i32> {
+        self.user_id
+    }
+
+    pub fn get_changed_at(&self) -> &DateTime<Utc> {
+        &self.changed_at
+    }
+
+    /// Fields whose value differs between the old and the new row. Creations list every
+    /// field with a null old value, and deletions with a null new value.
+    pub fn changes(&self) -> Vec<FieldChange> {
+        let old = parse_row(&self.old_value);
+        let mut new = parse_row(&self.new_value);
+
+        let mut res: Vec<FieldChange> = old
+            .into_iter()
+            .map(|(field, old)| {
+                let new = new.remove(&field).unwrap_or(Value::Null);
+                FieldChange { field, old, new }
+            })
+            .collect();
+        res.extend(new.into_iter().map(|(field, new)| FieldChange {
+            field,
+            old: Value::Null,
+            new,
+        }));
+        res.retain(|x| x.old != x.new);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditAction, AuditEntry};
+    use crate::models::{
+        account::{Account, AccountType},
+        categories::Category,
+        money::Money,
+        transaction::{Transaction, TransactionStatus},
+    };
+    use chrono::Utc;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn transaction_history_test() {
+        let pool = crate::create_db("sqlite://audit_test.db").await.unwrap();
+        let acc = Account::new(&pool, "audit_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let category = Category::new(&pool, "Food", "").await.unwrap();
+
+        let mut tx = Transaction::new(
+            &pool,
+            acc.get_id(),
+            "Groceries",
+            Utc::now().date_naive(),
+            None,
+            Money::from_cents(-1500),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+        let id = tx.get_id();
+        tx.set_category(&pool, Some(category.category_id))
+            .await
+            .unwrap();
+        let mut db_tx = super::begin(&pool, Some(7)).await.unwrap();
+        Transaction::get_by_id(&mut *db_tx, id)
+            .await
+            .unwrap()
+            .delete(&mut *db_tx)
+            .await
+            .unwrap();
+        // Changes rolled back are not logged, nor is their user left behind.
+        drop(db_tx);
+
+        let mut db_tx = super::begin(&pool, Some(7)).await.unwrap();
+        Account::get_by_id(&mut *db_tx, acc.get_id())
+            .await
+            .unwrap()
+            .set_archived(&mut *db_tx, true)
+            .await
+            .unwrap();
+        super::commit(db_tx).await.unwrap();
+
+        tx.set_amount(&pool, Money::from_cents(-1700))
+            .await
+            .unwrap();
+        tx.delete(&pool).await.unwrap();
+
+        let history = AuditEntry::list_for_row(&pool, "transactions", id)
+            .await
+            .unwrap();
+        let actions: Vec<AuditAction> = history.iter().map(|x| x.get_action()).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::Create,
+                AuditAction::Update,
+                AuditAction::Update,
+                AuditAction::Delete
+            ]
+        );
+
+        let changes = history[1].changes();
+        let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["category", "category_source"]);
+        assert_eq!(changes[0].new, json!(category.category_id));
+        assert_eq!(changes[1].new, json!("manual"));
+
+        let users: Vec<Option<i32>> = history.iter().map(|x| x.get_user()).collect();
+        assert_eq!(users, vec![None, None, None, None]);
+        let acc_history = AuditEntry::list_for_row(&pool, "accounts", acc.get_id())
+            .await
+            .unwrap();
+        let users: Vec<Option<i32>> = acc_history.iter().map(|x| x.get_user()).collect();
+        assert_eq!(users, vec![None, Some(7)]);
+
+        let changes = history[2].changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].old, json!(-1500));
+        assert_eq!(changes[0].new, json!(-1700));
+
+        pool.close().await;
+        std::fs::remove_file("audit_test.db").unwrap();
+    }
+}
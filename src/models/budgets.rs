@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Result, SqlitePool};
+
+use super::money::Money;
+
+/// First day of the month `date` belongs to.
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+/// Parses a `YYYY-MM` month.
+pub fn parse_month(s: &str) -> Option<NaiveDate> {
+    let (year, month) = s.split_once('-')?;
+    NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)
+}
+
+fn month_bounds(month: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let start = month_start(month);
+    (start, start + Months::new(1))
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct Budget {
+    budget_id: i32,
+    category: i32,
+    amount: Money,
+    rollover: bool,
+    start_month: NaiveDate,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BudgetStatus {
+    pub budget_id: i32,
+    pub category: i32,
+    pub amount: Money,
+    /// Unspent amount brought from previous months when rollover is enabled.
+    pub carried: Money,
+    /// Amount plus the carried amount.
+    pub available: Money,
+    pub spent: Money,
+    pub remaining: Money,
+    pub percent_used: f64,
+    pub overspent: bool,
+}
+
+impl Budget {
+    pub async fn new(
+        pool: &SqlitePool,
+        category: i32,
+        amount: Money,
+        rollover: bool,
+        start_month: NaiveDate,
+    ) -> Result<Self> {
+        sqlx::query(concat!(
+            "INSERT INTO budgets(category, amount, rollover, start_month) ",
+            "VALUES (?,?,?,?) RETURNING *"
+        ))
+        .bind(category)
+        .bind(amount)
+        .bind(rollover)
+        .bind(month_start(start_month))
+        .fetch_one(pool)
+        .await
+        .and_then(|r| Budget::from_row(&r))
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: i32) -> Result<Self> {
+        sqlx::query("SELECT * FROM budgets WHERE budget_id=?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .and_then(|r| Budget::from_row(&r))
+    }
+
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for r in sqlx::query("SELECT * FROM budgets")
+            .fetch_all(pool)
+            .await?
+            .iter()
+        {
+            res.push(Budget::from_row(r)?)
+        }
+        Ok(res)
+    }
+
+    pub async fn delete(self, pool: &SqlitePool) -> Result<()> {
+        sqlx::query("DELETE FROM budgets WHERE budget_id=?")
+            .bind(self.budget_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub fn get_id(&self) -> i32 {
+        self.budget_id
+    }
+
+    pub fn get_category(&self) -> i32 {
+        self.category
+    }
+
+    pub fn get_amount(&self) -> Money {
+        self.amount
+    }
+
+    pub fn get_rollover(&self) -> bool {
+        self.rollover
+    }
+
+    pub async fn set_amount(&mut self, pool: &SqlitePool, amount: Money) -> Result<()> {
+        sqlx::query("UPDATE budgets SET amount=? WHERE budget_id=?")
+            .bind(amount)
+            .bind(self.budget_id)
+            .execute(pool)
+            .await?;
+        self.amount = amount;
+        Ok(())
+    }
+
+    pub async fn set_rollover(&mut self, pool: &SqlitePool, rollover: bool) -> Result<()> {
+        sqlx::query("UPDATE budgets SET rollover=? WHERE budget_id=?")
+            .bind(rollover)
+            .bind(self.budget_id)
+            .execute(pool)
+            .await?;
+        self.rollover = rollover;
+        Ok(())
+    }
+
+    /// Expenses of every category during the calendar month of `month`, counted like the
+    /// dashboard does: the opposite of the negative amounts.
+    pub async fn spending_by_category(
+        pool: &SqlitePool,
+        month: NaiveDate,
+    ) -> Result<HashMap<i32, Money>> {
+        let (start, end) = month_bounds(month);
+        let rows: Vec<(Option<i32>, Money)> = sqlx::query_as(concat!(
+            "SELECT category, SUM(-amount) FROM transactions ",
+            "WHERE amount < 0 AND tx_date >= ? AND tx_date < ? ",
+            "GROUP BY category"
+        ))
+        .bind(start)
+        .bind(end)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(c, s)| (c.unwrap_or(0), s)).collect())
+    }
+
+    /// Unspent budget carried into `month`. Overspending is not carried, so a bad month
+    /// does not eat into the following ones.
+    async fn carried_into(&self, pool: &SqlitePool, month: NaiveDate) -> Result<Money> {
+        if !self.rollover || month <= self.start_month {
+            return Ok(Money::ZERO);
+        }
+
+        let (start, _) = month_bounds(self.start_month);
+        let (end, _) = month_bounds(month);
+        let spent: HashMap<String, Money> = sqlx::query_as(concat!(
+            "SELECT strftime('%Y-%m', tx_date) AS month, SUM(-amount) FROM transactions ",
+            "WHERE category=? AND amount < 0 AND tx_date >= ? AND tx_date < ? ",
+            "GROUP BY month"
+        ))
+        .bind(self.category)
+        .bind(start)
+        .bind(end)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+
+        let mut carried = Money::ZERO;
+        let mut m = self.start_month;
+        while m < month_start(month) {
+            let key = m.format("%Y-%m").to_string();
+            let spent = spent.get(&key).copied().unwrap_or_default();
+            carried = (carried + self.amount - spent).max(Money::ZERO);
+            m = m + Months::new(1);
+        }
+        Ok(carried)
+    }
+
+    pub async fn status(&self, pool: &SqlitePool, month: NaiveDate) -> Result<BudgetStatus> {
+        let spent = Self::spending_by_category(pool, month)
+            .await?
+            .get(&self.category)
+            .copied()
+            .unwrap_or_default();
+        self.status_with_spent(pool, month, spent).await
+    }
+
+    async fn status_with_spent(
+        &self,
+        pool: &SqlitePool,
+        month: NaiveDate,
+        spent: Money,
+    ) -> Result<BudgetStatus> {
+        let carried = self.carried_into(pool, month).await?;
+        let available = self.amount + carried;
+        Ok(BudgetStatus {
+            budget_id: self.budget_id,
+            category: self.category,
+            amount: self.amount,
+            carried,
+            available,
+            spent,
+            remaining: available - spent,
+            percent_used: if available.is_positive() {
+                spent.cents() as f64 * 100.0 / available.cents() as f64
+            } else {
+                0.0
+            },
+            overspent: spent > available,
+        })
+    }
+
+    /// Status of every budget that applies to `month`.
+    pub async fn list_status(pool: &SqlitePool, month: NaiveDate) -> Result<Vec<BudgetStatus>> {
+        let spending = Self::spending_by_category(pool, month).await?;
+        let mut res = Vec::new();
+        for b in Self::list(pool).await? {
+            if b.start_month > month {
+                continue;
+            }
+            let spent = spending.get(&b.category).copied().unwrap_or_default();
+            res.push(b.status_with_spent(pool, month, spent).await?);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Budget;
+    use crate::models::{
+        account::{Account, AccountType},
+        categories::Category,
+        money::Money,
+        transaction::{Transaction, TransactionStatus},
+    };
+    use chrono::NaiveDate;
+
+    #[tokio::test]
+    async fn rollover_test() {
+        let pool = crate::create_db("sqlite://budget_test.db").await.unwrap();
+        let acc = Account::new(&pool, "budget_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let food = Category::new(&pool, "Food", "").await.unwrap();
+
+        for (month, amount) in [(1, -20000), (2, -35000), (3, -25000)] {
+            Transaction::new(
+                &pool,
+                acc.get_id(),
+                "Groceries",
+                NaiveDate::from_ymd_opt(2024, month, 10).unwrap(),
+                Some(food.category_id),
+                Money::from_cents(amount),
+                TransactionStatus::Cleared,
+            )
+            .await
+            .unwrap();
+        }
+
+        let budget = Budget::new(
+            &pool,
+            food.category_id,
+            Money::from_cents(30000),
+            true,
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        // January leaves 100 unspent, February spends it and 50 more, which is not carried.
+        let status = budget
+            .status(&pool, NaiveDate::from_ymd_opt(2024, 2, 1).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(status.carried, Money::from_cents(10000));
+        assert_eq!(status.remaining, Money::from_cents(5000));
+        assert!(!status.overspent);
+
+        let status = budget
+            .status(&pool, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(status.carried, Money::from_cents(5000));
+        assert_eq!(status.spent, Money::from_cents(25000));
+        assert_eq!(status.remaining, Money::from_cents(10000));
+
+        pool.close().await;
+        std::fs::remove_file("budget_test.db").unwrap();
+    }
+}
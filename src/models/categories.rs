@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, SqlitePool};
+use sqlx::{Executor, FromRow, Sqlite, SqlitePool};
 
 #[derive(FromRow, Serialize, Deserialize)]
 pub struct Category {
@@ -17,6 +17,19 @@ impl Category {
             .and_then(|r| Category::from_row(&r))
     }
 
+    /// Looks a category up by its name, ignoring case.
+    pub async fn get_by_name(
+        conn: impl Executor<'_, Database = Sqlite>,
+        name: &str,
+    ) -> sqlx::Result<Option<Self>> {
+        sqlx::query("SELECT * FROM categories WHERE name=? COLLATE NOCASE ORDER BY category_id")
+            .bind(name.trim())
+            .fetch_optional(conn)
+            .await?
+            .map(|r| Category::from_row(&r))
+            .transpose()
+    }
+
     pub async fn list(pool: &SqlitePool) -> sqlx::Result<Vec<Category>> {
         let mut res = Vec::new();
         for r in sqlx::query("SELECT * FROM categories")
@@ -30,11 +43,15 @@ impl Category {
         Ok(res)
     }
 
-    pub async fn new(pool: &SqlitePool, name: &str, description: &str) -> sqlx::Result<Category> {
+    pub async fn new(
+        conn: impl Executor<'_, Database = Sqlite>,
+        name: &str,
+        description: &str,
+    ) -> sqlx::Result<Category> {
         sqlx::query("INSERT INTO categories(name, description) VALUES (?,?) RETURNING *")
             .bind(name)
             .bind(description)
-            .fetch_one(pool)
+            .fetch_one(conn)
             .await
             .and_then(|r| Category::from_row(&r))
     }
@@ -0,0 +1,200 @@
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use sqlx::{Result, SqlitePool};
+
+use super::{account::Account, money::Money, scheduled::ScheduledTransaction};
+
+pub const DEFAULT_FORECAST_DAYS: u32 = 60;
+pub const MAX_FORECAST_DAYS: u32 = 366;
+
+/// Days of history used to estimate the average spending per category.
+const SPENDING_WINDOW_DAYS: i64 = 90;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CategorySpending {
+    pub category: Option<i32>,
+    /// Average spent per day, in cents.
+    pub daily: f64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ForecastPoint {
+    pub date: NaiveDate,
+    pub balance: Money,
+    /// Amount coming from scheduled transactions on this day.
+    pub scheduled: Money,
+    /// Estimated amount of unscheduled spending on this day.
+    pub estimated: Money,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Forecast {
+    pub account: i32,
+    pub start_balance: Money,
+    pub spending: Vec<CategorySpending>,
+    pub points: Vec<ForecastPoint>,
+    pub lowest: Option<ForecastPoint>,
+    pub first_negative: Option<NaiveDate>,
+}
+
+impl Forecast {
+    /// Projects `days` daily balances after `today`, adding the `scheduled` amounts on
+    /// their dates and spending the sum of `spending` every day.
+    pub fn project(
+        account: i32,
+        start_balance: Money,
+        today: NaiveDate,
+        days: u32,
+        scheduled: &[(NaiveDate, Money)],
+        spending: Vec<CategorySpending>,
+    ) -> Self {
+        let daily: f64 = spending.iter().map(|x| x.daily).sum();
+        let mut points = Vec::with_capacity(days as usize);
+        let mut balance = start_balance;
+        // Keeps the fractions of a cent so that small categories still add up over time.
+        let mut estimated_total: f64 = 0.0;
+
+        for day in 1..=days as i64 {
+            let date = today + Duration::days(day);
+            let scheduled: Money = scheduled
+                .iter()
+                .filter(|(d, _)| *d == date)
+                .map(|(_, a)| *a)
+                .sum();
+            let before = Money::from_cents_f64(estimated_total);
+            estimated_total += daily;
+            let estimated = before - Money::from_cents_f64(estimated_total);
+
+            balance += scheduled + estimated;
+            points.push(ForecastPoint {
+                date,
+                balance,
+                scheduled,
+                estimated,
+            });
+        }
+
+        let lowest = points.iter().min_by_key(|x| x.balance).cloned();
+        let first_negative = points
+            .iter()
+            .find(|x| x.balance.is_negative())
+            .map(|x| x.date);
+
+        Forecast {
+            account,
+            start_balance,
+            spending,
+            points,
+            lowest,
+            first_negative,
+        }
+    }
+
+    /// Average daily spending per category over the last days. Transactions created from a
+    /// schedule are left out, as they are already accounted for by their schedule.
+    pub async fn average_spending(
+        pool: &SqlitePool,
+        account: i32,
+        today: NaiveDate,
+    ) -> Result<Vec<CategorySpending>> {
+        let start = today - Duration::days(SPENDING_WINDOW_DAYS);
+        let rows: Vec<(Option<i32>, Money)> = sqlx::query_as(concat!(
+            "SELECT category, SUM(-amount) FROM transactions ",
+            "WHERE account=? AND amount < 0 AND scheduled IS NULL ",
+            "AND tx_date >= ? AND tx_date < ? ",
+            "GROUP BY category"
+        ))
+        .bind(account)
+        .bind(start)
+        .bind(today)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(category, spent)| CategorySpending {
+                category,
+                daily: spent.cents() as f64 / SPENDING_WINDOW_DAYS as f64,
+            })
+            .collect())
+    }
+
+    pub async fn for_account(
+        pool: &SqlitePool,
+        account: &Account,
+        today: NaiveDate,
+        days: u32,
+    ) -> Result<Self> {
+        let until = today + Duration::days(days as i64);
+        let mut scheduled = Vec::new();
+        for s in ScheduledTransaction::list_by_account(pool, account.get_id()).await? {
+            // Occurrences up to today are created as pending transactions, so they are
+            // already part of the balance.
+            for date in s
+                .occurrences_until(until)
+                .into_iter()
+                .filter(|d| *d > today)
+            {
+                scheduled.push((date, s.get_amount()));
+            }
+        }
+
+        Ok(Self::project(
+            account.get_id(),
+            account.get_balance(pool).await?,
+            today,
+            days,
+            &scheduled,
+            Self::average_spending(pool, account.get_id(), today).await?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CategorySpending, Forecast};
+    use crate::models::money::Money;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn project_test() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let payday = NaiveDate::from_ymd_opt(2024, 3, 25).unwrap();
+        let rent = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let spending = vec![
+            CategorySpending {
+                category: Some(1),
+                daily: 1000.0,
+            },
+            CategorySpending {
+                category: None,
+                daily: 250.5,
+            },
+        ];
+
+        let forecast = Forecast::project(
+            1,
+            Money::from_cents(95000),
+            today,
+            20,
+            &[
+                (rent, Money::from_cents(-90000)),
+                (payday, Money::from_cents(200000)),
+            ],
+            spending,
+        );
+        assert_eq!(forecast.points.len(), 20);
+        assert_eq!(forecast.points[0].estimated, Money::from_cents(-1251));
+        assert_eq!(forecast.points[1].estimated, Money::from_cents(-1250));
+
+        // 5 days of spending and the rent leave the account short before payday.
+        assert_eq!(forecast.first_negative, Some(rent));
+        let lowest = forecast.lowest.unwrap();
+        assert_eq!(lowest.date, payday.pred_opt().unwrap());
+        assert_eq!(forecast.points[14].scheduled, Money::from_cents(200000));
+        assert_eq!(
+            forecast.points[19].balance,
+            Money::from_cents(95000 - 90000 + 200000 - 25010)
+        );
+    }
+}
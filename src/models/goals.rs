@@ -0,0 +1,218 @@
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Result, SqlitePool};
+
+use super::{account::Account, money::Money};
+
+/// Days of history used to estimate how fast a goal is growing.
+const GROWTH_WINDOW_DAYS: i64 = 90;
+
+#[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct Goal {
+    goal_id: i32,
+    goal_name: String,
+    account: i32,
+    /// Percentage of the account balance assigned to this goal.
+    share: i32,
+    target_amount: Money,
+    target_date: NaiveDate,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GoalProgress {
+    #[serde(flatten)]
+    pub goal: Goal,
+    pub current: Money,
+    pub remaining: Money,
+    pub percent: f64,
+    /// Contribution needed every month to reach the target on time.
+    pub monthly_needed: Money,
+    /// Date the target is reached at the pace of the last months, if it is growing at all.
+    pub projected_date: Option<NaiveDate>,
+    pub on_track: bool,
+}
+
+fn months_between(from: NaiveDate, to: NaiveDate) -> i64 {
+    (to.year() - from.year()) as i64 * 12 + to.month() as i64 - from.month() as i64
+}
+
+impl Goal {
+    pub async fn new(
+        pool: &SqlitePool,
+        name: &str,
+        account: i32,
+        share: i32,
+        target_amount: Money,
+        target_date: NaiveDate,
+    ) -> Result<Self> {
+        if !(1..=100).contains(&share) {
+            return Err(sqlx::Error::Protocol(format!(
+                "Goal share must be between 1 and 100, got {share}"
+            )));
+        }
+
+        sqlx::query(concat!(
+            "INSERT INTO goals(goal_name, account, share, target_amount, target_date) ",
+            "VALUES (?,?,?,?,?) RETURNING *"
+        ))
+        .bind(name)
+        .bind(account)
+        .bind(share)
+        .bind(target_amount)
+        .bind(target_date)
+        .fetch_one(pool)
+        .await
+        .and_then(|r| Goal::from_row(&r))
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: i32) -> Result<Self> {
+        sqlx::query("SELECT * FROM goals WHERE goal_id=?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .and_then(|r| Goal::from_row(&r))
+    }
+
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for r in sqlx::query("SELECT * FROM goals ORDER BY target_date")
+            .fetch_all(pool)
+            .await?
+            .iter()
+        {
+            res.push(Goal::from_row(r)?)
+        }
+        Ok(res)
+    }
+
+    pub async fn delete(self, pool: &SqlitePool) -> Result<()> {
+        sqlx::query("DELETE FROM goals WHERE goal_id=?")
+            .bind(self.goal_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub fn get_id(&self) -> i32 {
+        self.goal_id
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.goal_name.as_str()
+    }
+
+    pub fn get_account(&self) -> i32 {
+        self.account
+    }
+
+    pub fn get_share(&self) -> i32 {
+        self.share
+    }
+
+    pub fn get_target_amount(&self) -> Money {
+        self.target_amount
+    }
+
+    pub fn get_target_date(&self) -> NaiveDate {
+        self.target_date
+    }
+
+    fn share_of(&self, balance: Money) -> Money {
+        Money::from_cents(balance.cents() * self.share as i64 / 100)
+    }
+
+    /// Computes the progress given the current amount saved and how many cents it grows
+    /// per day.
+    pub fn progress(self, current: Money, daily_growth: f64, today: NaiveDate) -> GoalProgress {
+        let current = current.cents().max(0);
+        let target = self.target_amount.cents();
+        let remaining = (target - current).max(0);
+
+        let months_left = months_between(today, self.target_date).max(1);
+        let monthly_needed = (remaining + months_left - 1) / months_left;
+
+        let projected_date = if remaining == 0 {
+            Some(today)
+        } else if daily_growth > 0.0 {
+            today.checked_add_signed(Duration::days(
+                (remaining as f64 / daily_growth).ceil() as i64
+            ))
+        } else {
+            None
+        };
+        let on_track = projected_date.is_some_and(|x| x <= self.target_date);
+
+        GoalProgress {
+            current: Money::from_cents(current),
+            remaining: Money::from_cents(remaining),
+            percent: if target > 0 {
+                (current as f64 * 100.0 / target as f64).min(100.0)
+            } else {
+                100.0
+            },
+            monthly_needed: Money::from_cents(monthly_needed),
+            projected_date,
+            on_track,
+            goal: self,
+        }
+    }
+
+    pub async fn get_progress(self, pool: &SqlitePool) -> Result<GoalProgress> {
+        let account = Account::get_by_id(pool, self.account).await?;
+        let today = Utc::now().date_naive();
+        let balance = account.get_balance(pool).await?;
+        let before = account
+            .get_balance_at(pool, today - Duration::days(GROWTH_WINDOW_DAYS))
+            .await?;
+
+        let current = self.share_of(balance);
+        let daily_growth =
+            (current - self.share_of(before)).cents() as f64 / GROWTH_WINDOW_DAYS as f64;
+        Ok(self.progress(current, daily_growth, today))
+    }
+
+    pub async fn list_progress(pool: &SqlitePool) -> Result<Vec<GoalProgress>> {
+        let mut res = Vec::new();
+        for goal in Self::list(pool).await? {
+            res.push(goal.get_progress(pool).await?);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Goal;
+    use crate::models::money::Money;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn progress_test() {
+        let goal = Goal {
+            goal_id: 1,
+            goal_name: String::from("Car"),
+            account: 1,
+            share: 50,
+            target_amount: Money::from_cents(1200000),
+            target_date: NaiveDate::from_ymd_opt(2024, 12, 31).unwrap(),
+        };
+        let today = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+
+        // 6000 saved out of 12000, 6 months left and growing 40 a day.
+        let progress = goal.progress(Money::from_cents(600000), 4000.0, today);
+        assert_eq!(progress.remaining, Money::from_cents(600000));
+        assert_eq!(progress.monthly_needed, Money::from_cents(100000));
+        assert_eq!(progress.percent, 50.0);
+        assert_eq!(
+            progress.projected_date,
+            NaiveDate::from_ymd_opt(2024, 11, 12)
+        );
+        assert!(progress.on_track);
+
+        let progress = progress
+            .goal
+            .progress(Money::from_cents(600000), 0.0, today);
+        assert_eq!(progress.projected_date, None);
+        assert!(!progress.on_track);
+    }
+}
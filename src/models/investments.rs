@@ -0,0 +1,694 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Result, SqliteConnection, SqlitePool};
+
+use super::{
+    account::{Account, AccountType},
+    money::Money,
+};
+
+/// Quantities below this are treated as zero, to absorb floating point noise.
+const QUANTITY_EPSILON: f64 = 1e-9;
+
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct Security {
+    security_id: i32,
+    symbol: String,
+    security_name: String,
+}
+
+impl Security {
+    pub async fn new(pool: &SqlitePool, symbol: &str, name: &str) -> Result<Self> {
+        sqlx::query("INSERT INTO securities(symbol, security_name) VALUES (?,?) RETURNING *")
+            .bind(symbol)
+            .bind(name)
+            .fetch_one(pool)
+            .await
+            .and_then(|r| Security::from_row(&r))
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: i32) -> Result<Self> {
+        sqlx::query("SELECT * FROM securities WHERE security_id=?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .and_then(|r| Security::from_row(&r))
+    }
+
+    pub async fn get_by_symbol(pool: &SqlitePool, symbol: &str) -> Result<Option<Self>> {
+        match sqlx::query("SELECT * FROM securities WHERE symbol=?")
+            .bind(symbol)
+            .fetch_optional(pool)
+            .await?
+        {
+            Some(r) => Ok(Some(Security::from_row(&r)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for r in sqlx::query("SELECT * FROM securities ORDER BY symbol")
+            .fetch_all(pool)
+            .await?
+            .iter()
+        {
+            res.push(Security::from_row(r)?)
+        }
+        Ok(res)
+    }
+
+    pub fn get_id(&self) -> i32 {
+        self.security_id
+    }
+
+    pub fn get_symbol(&self) -> &str {
+        self.symbol.as_str()
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.security_name.as_str()
+    }
+
+    pub async fn set_price(&self, pool: &SqlitePool, date: NaiveDate, price: Money) -> Result<()> {
+        sqlx::query(concat!(
+            "INSERT INTO security_prices(security, price_date, price) VALUES (?,?,?) ",
+            "ON CONFLICT(security, price_date) DO UPDATE SET price=excluded.price"
+        ))
+        .bind(self.security_id)
+        .bind(date)
+        .bind(price)
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+
+    /// Latest known price on or before `date`.
+    pub async fn price_at(&self, pool: &SqlitePool, date: NaiveDate) -> Result<Option<Money>> {
+        sqlx::query_scalar(concat!(
+            "SELECT price FROM security_prices WHERE security=? AND price_date<=? ",
+            "ORDER BY price_date DESC LIMIT 1"
+        ))
+        .bind(self.security_id)
+        .bind(date)
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Loads a price history from CSV lines with the format `symbol,date,price`, where
+    /// the date is `YYYY-MM-DD` and the price is in units, like `12.34`. A header line is
+    /// allowed. Unknown symbols are created. Returns the number of prices loaded.
+    pub async fn import_prices_csv(pool: &SqlitePool, content: &str) -> Result<usize> {
+        let mut prices = Vec::new();
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+            let parsed = match fields[..] {
+                [symbol, date, price] => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .ok()
+                    .zip(price.parse::<Money>().ok())
+                    .map(|(d, p)| (symbol.to_string(), d, p)),
+                _ => None,
+            };
+            match parsed {
+                Some(p) => prices.push(p),
+                // The first line may be a header.
+                None if n == 0 => continue,
+                None => {
+                    return Err(sqlx::Error::Protocol(format!(
+                        "Invalid price at line {}: {line}",
+                        n + 1
+                    )))
+                }
+            }
+        }
+
+        let mut securities: HashMap<String, Security> = HashMap::new();
+        for (symbol, date, price) in prices.iter() {
+            if !securities.contains_key(symbol) {
+                let security = match Self::get_by_symbol(pool, symbol).await? {
+                    Some(s) => s,
+                    None => Self::new(pool, symbol, symbol).await?,
+                };
+                securities.insert(symbol.clone(), security);
+            }
+            securities[symbol].set_price(pool, *date, *price).await?;
+        }
+        Ok(prices.len())
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TradeKind {
+    Buy,
+    Sell,
+    Dividend,
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct Trade {
+    trade_id: i32,
+    account: i32,
+    security: i32,
+    trade_date: NaiveDate,
+    kind: TradeKind,
+    quantity: f64,
+    /// Price per unit.
+    price: Money,
+    fees: Money,
+    amount: Money,
+}
+
+async fn check_investment_account(pool: &SqlitePool, account: i32) -> Result<()> {
+    let acc = Account::get_by_id(pool, account).await?;
+    if acc.get_account_type() != AccountType::Investment {
+        return Err(sqlx::Error::Protocol(format!(
+            "Account {account} is not an investment account"
+        )));
+    }
+    Ok(())
+}
+
+/// Fails if the units held of `security` go below zero after any trade, which a sale
+/// dated before its buys or the removal of a buy would cause. Trades are taken in the
+/// order the portfolio replays them, so a sale entered before a buy of the same day
+/// cannot rely on it.
+async fn check_position(conn: &mut SqliteConnection, account: i32, security: i32) -> Result<()> {
+    let lowest: Option<(NaiveDate, f64)> = sqlx::query_as(concat!(
+        "SELECT trade_date, held FROM (",
+        "SELECT trade_date, SUM(CASE kind WHEN 'buy' THEN quantity ",
+        "WHEN 'sell' THEN -quantity ELSE 0 END) OVER (",
+        "ORDER BY trade_date, trade_id ROWS UNBOUNDED PRECEDING) AS held ",
+        "FROM trades WHERE account=? AND security=?",
+        ") ORDER BY held, trade_date LIMIT 1"
+    ))
+    .bind(account)
+    .bind(security)
+    .fetch_optional(conn)
+    .await?;
+    match lowest {
+        Some((date, held)) if held < -QUANTITY_EPSILON => Err(sqlx::Error::Protocol(format!(
+            "Not enough units held, the position would be {held} on {date}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+impl Trade {
+    /// Records a buy or a sale of `quantity` units at `price` each.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new(
+        pool: &SqlitePool,
+        account: i32,
+        security: i32,
+        date: NaiveDate,
+        kind: TradeKind,
+        quantity: f64,
+        price: Money,
+        fees: Money,
+    ) -> Result<Self> {
+        if kind == TradeKind::Dividend {
+            return Err(sqlx::Error::Protocol(String::from(
+                "Dividends are recorded with Trade::dividend",
+            )));
+        }
+        if quantity <= 0.0 {
+            return Err(sqlx::Error::Protocol(format!(
+                "Trade quantity must be positive, got {quantity}"
+            )));
+        }
+
+        let gross = Money::from_cents_f64(quantity * price.cents() as f64);
+        let amount = match kind {
+            TradeKind::Buy => -gross - fees,
+            _ => gross - fees,
+        };
+
+        check_investment_account(pool, account).await?;
+        let mut tx = pool.begin().await?;
+        let trade = sqlx::query(concat!(
+            "INSERT INTO trades(account, security, trade_date, kind, quantity, price, fees, amount) ",
+            "VALUES (?,?,?,?,?,?,?,?) RETURNING *"
+        ))
+        .bind(account)
+        .bind(security)
+        .bind(date)
+        .bind(kind)
+        .bind(quantity)
+        .bind(price)
+        .bind(fees)
+        .bind(amount)
+        .fetch_one(&mut *tx)
+        .await
+        .and_then(|r| Trade::from_row(&r))?;
+        if kind == TradeKind::Sell {
+            check_position(&mut tx, account, security).await?;
+        }
+        tx.commit().await?;
+        Ok(trade)
+    }
+
+    pub async fn dividend(
+        pool: &SqlitePool,
+        account: i32,
+        security: i32,
+        date: NaiveDate,
+        amount: Money,
+    ) -> Result<Self> {
+        check_investment_account(pool, account).await?;
+        sqlx::query(concat!(
+            "INSERT INTO trades(account, security, trade_date, kind, amount) ",
+            "VALUES (?,?,?,?,?) RETURNING *"
+        ))
+        .bind(account)
+        .bind(security)
+        .bind(date)
+        .bind(TradeKind::Dividend)
+        .bind(amount)
+        .fetch_one(pool)
+        .await
+        .and_then(|r| Trade::from_row(&r))
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: i32) -> Result<Self> {
+        sqlx::query("SELECT * FROM trades WHERE trade_id=?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .and_then(|r| Trade::from_row(&r))
+    }
+
+    pub async fn list_by_account(pool: &SqlitePool, account: i32) -> Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for r in sqlx::query("SELECT * FROM trades WHERE account=? ORDER BY trade_date, trade_id")
+            .bind(account)
+            .fetch_all(pool)
+            .await?
+            .iter()
+        {
+            res.push(Trade::from_row(r)?)
+        }
+        Ok(res)
+    }
+
+    pub async fn delete(self, pool: &SqlitePool) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM trades WHERE trade_id=?")
+            .bind(self.trade_id)
+            .execute(&mut *tx)
+            .await?;
+        if self.kind == TradeKind::Buy {
+            check_position(&mut tx, self.account, self.security).await?;
+        }
+        tx.commit().await
+    }
+
+    pub fn get_id(&self) -> i32 {
+        self.trade_id
+    }
+
+    pub fn get_security(&self) -> i32 {
+        self.security
+    }
+
+    pub fn get_date(&self) -> NaiveDate {
+        self.trade_date
+    }
+
+    pub fn get_kind(&self) -> TradeKind {
+        self.kind
+    }
+
+    pub fn get_quantity(&self) -> f64 {
+        self.quantity
+    }
+
+    pub fn get_price(&self) -> Money {
+        self.price
+    }
+
+    pub fn get_amount(&self) -> Money {
+        self.amount
+    }
+}
+
+/// Units bought in a single trade that have not been sold yet.
+#[derive(Serialize, Debug, Clone)]
+pub struct Lot {
+    pub trade: i32,
+    pub date: NaiveDate,
+    pub quantity: f64,
+    /// Cost per unit including the fees of the purchase, in cents.
+    pub unit_cost: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Holding {
+    pub security: i32,
+    pub symbol: String,
+    pub quantity: f64,
+    pub cost_basis: Money,
+    pub price: Option<Money>,
+    pub market_value: Option<Money>,
+    pub unrealized_gain: Option<Money>,
+    pub realized_gain: Money,
+    pub dividends: Money,
+    pub lots: Vec<Lot>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Portfolio {
+    pub account: i32,
+    pub holdings: Vec<Holding>,
+    pub cost_basis: Money,
+    pub market_value: Money,
+    pub realized_gain: Money,
+    pub unrealized_gain: Money,
+    pub dividends: Money,
+}
+
+#[derive(Default)]
+struct Position {
+    lots: VecDeque<Lot>,
+    realized_gain: f64,
+    dividends: Money,
+    last_price: Option<Money>,
+}
+
+impl Position {
+    fn apply(&mut self, trade: &Trade) {
+        match trade.kind {
+            TradeKind::Buy => {
+                self.lots.push_back(Lot {
+                    trade: trade.trade_id,
+                    date: trade.trade_date,
+                    quantity: trade.quantity,
+                    unit_cost: -trade.amount.cents() as f64 / trade.quantity,
+                });
+                self.last_price = Some(trade.price);
+            }
+            TradeKind::Sell => {
+                let mut left = trade.quantity;
+                let mut cost = 0.0;
+                while left > QUANTITY_EPSILON {
+                    let Some(lot) = self.lots.front_mut() else {
+                        break;
+                    };
+                    let used = lot.quantity.min(left);
+                    cost += used * lot.unit_cost;
+                    lot.quantity -= used;
+                    left -= used;
+                    if lot.quantity <= QUANTITY_EPSILON {
+                        self.lots.pop_front();
+                    }
+                }
+                self.realized_gain += trade.amount.cents() as f64 - cost;
+                self.last_price = Some(trade.price);
+            }
+            TradeKind::Dividend => self.dividends += trade.amount,
+        }
+    }
+}
+
+impl Portfolio {
+    /// Builds the holdings from the trades of an account, in the order they happened,
+    /// matching sales against the oldest lots first. `prices` has the current price of
+    /// each security; the last trade price is used for the ones missing.
+    pub fn build(
+        account: i32,
+        trades: &[Trade],
+        securities: &HashMap<i32, Security>,
+        prices: &HashMap<i32, Money>,
+    ) -> Self {
+        let mut positions: HashMap<i32, Position> = HashMap::new();
+        for trade in trades.iter() {
+            positions.entry(trade.security).or_default().apply(trade);
+        }
+
+        let mut holdings: Vec<Holding> = positions
+            .into_iter()
+            .map(|(security, pos)| {
+                let quantity: f64 = pos.lots.iter().map(|x| x.quantity).sum();
+                let cost_basis =
+                    Money::from_cents_f64(pos.lots.iter().map(|x| x.quantity * x.unit_cost).sum());
+                let price = prices.get(&security).copied().or(pos.last_price);
+                let market_value =
+                    price.map(|p| Money::from_cents_f64(quantity * p.cents() as f64));
+                Holding {
+                    security,
+                    symbol: securities
+                        .get(&security)
+                        .map(|x| x.symbol.clone())
+                        .unwrap_or_default(),
+                    quantity,
+                    cost_basis,
+                    price,
+                    market_value,
+                    unrealized_gain: market_value.map(|x| x - cost_basis),
+                    realized_gain: Money::from_cents_f64(pos.realized_gain),
+                    dividends: pos.dividends,
+                    lots: pos.lots.into(),
+                }
+            })
+            .collect();
+        holdings.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+        Portfolio {
+            account,
+            cost_basis: holdings.iter().map(|x| x.cost_basis).sum(),
+            market_value: holdings.iter().filter_map(|x| x.market_value).sum(),
+            realized_gain: holdings.iter().map(|x| x.realized_gain).sum(),
+            unrealized_gain: holdings.iter().filter_map(|x| x.unrealized_gain).sum(),
+            dividends: holdings.iter().map(|x| x.dividends).sum(),
+            holdings,
+        }
+    }
+
+    pub async fn for_account(pool: &SqlitePool, account: i32, today: NaiveDate) -> Result<Self> {
+        let trades = Trade::list_by_account(pool, account).await?;
+        let mut securities = HashMap::new();
+        let mut prices = HashMap::new();
+        for trade in trades.iter() {
+            if securities.contains_key(&trade.security) {
+                continue;
+            }
+            let security = Security::get_by_id(pool, trade.security).await?;
+            if let Some(price) = security.price_at(pool, today).await? {
+                prices.insert(trade.security, price);
+            }
+            securities.insert(trade.security, security);
+        }
+        Ok(Self::build(account, &trades, &securities, &prices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Portfolio, Security, Trade, TradeKind};
+    use crate::models::{
+        account::{Account, AccountType},
+        money::Money,
+    };
+    use chrono::NaiveDate;
+
+    fn trade(id: i32, day: u32, kind: TradeKind, quantity: f64, price: i64, amount: i64) -> Trade {
+        Trade {
+            trade_id: id,
+            account: 1,
+            security: 1,
+            trade_date: NaiveDate::from_ymd_opt(2023, 1, day).unwrap(),
+            kind,
+            quantity,
+            price: Money::from_cents(price),
+            fees: Money::ZERO,
+            amount: Money::from_cents(amount),
+        }
+    }
+
+    #[test]
+    fn fifo_test() {
+        let trades = vec![
+            trade(1, 1, TradeKind::Buy, 10.0, 1000, -10100),
+            trade(2, 2, TradeKind::Buy, 10.0, 1200, -12000),
+            trade(3, 3, TradeKind::Sell, 15.0, 1500, 22400),
+            trade(4, 4, TradeKind::Dividend, 0.0, 0, 300),
+        ];
+        let securities = HashMap::from([(
+            1,
+            Security {
+                security_id: 1,
+                symbol: String::from("ACME"),
+                security_name: String::from("Acme"),
+            },
+        )]);
+
+        let portfolio = Portfolio::build(
+            1,
+            &trades,
+            &securities,
+            &HashMap::from([(1, Money::from_cents(1400))]),
+        );
+        let holding = &portfolio.holdings[0];
+        // The sale takes the 10 units of the first lot and 5 of the second.
+        assert_eq!(holding.lots.len(), 1);
+        assert_eq!(holding.lots[0].trade, 2);
+        assert_eq!(holding.quantity, 5.0);
+        assert_eq!(holding.cost_basis, Money::from_cents(6000));
+        assert_eq!(
+            holding.realized_gain,
+            Money::from_cents(22400 - 10100 - 6000)
+        );
+        assert_eq!(holding.market_value, Some(Money::from_cents(7000)));
+        assert_eq!(holding.unrealized_gain, Some(Money::from_cents(1000)));
+        assert_eq!(portfolio.dividends, Money::from_cents(300));
+
+        // Without a price history, the last trade price is used.
+        let portfolio = Portfolio::build(1, &trades, &securities, &HashMap::new());
+        assert_eq!(portfolio.market_value, Money::from_cents(7500));
+    }
+
+    #[tokio::test]
+    async fn position_test() {
+        let pool = crate::create_db("sqlite://investments_position_test.db")
+            .await
+            .unwrap();
+        let acc = Account::new(&pool, "broker", AccountType::Investment, None)
+            .await
+            .unwrap();
+        let etf = Security::new(&pool, "VWCE", "FTSE All-World")
+            .await
+            .unwrap();
+        let date = |d| NaiveDate::from_ymd_opt(2023, 3, d).unwrap();
+        let price = Money::from_cents(10000);
+        let fees = Money::ZERO;
+
+        let buy = Trade::new(
+            &pool,
+            acc.get_id(),
+            etf.get_id(),
+            date(10),
+            TradeKind::Buy,
+            10.0,
+            price,
+            fees,
+        )
+        .await
+        .unwrap();
+        Trade::new(
+            &pool,
+            acc.get_id(),
+            etf.get_id(),
+            date(20),
+            TradeKind::Sell,
+            6.0,
+            price,
+            fees,
+        )
+        .await
+        .unwrap();
+
+        // A backdated sale cannot leave too few units for the one recorded on the 20th.
+        assert!(Trade::new(
+            &pool,
+            acc.get_id(),
+            etf.get_id(),
+            date(15),
+            TradeKind::Sell,
+            5.0,
+            price,
+            fees
+        )
+        .await
+        .is_err());
+        assert!(Trade::new(
+            &pool,
+            acc.get_id(),
+            etf.get_id(),
+            date(5),
+            TradeKind::Sell,
+            1.0,
+            price,
+            fees
+        )
+        .await
+        .is_err());
+        Trade::new(
+            &pool,
+            acc.get_id(),
+            etf.get_id(),
+            date(15),
+            TradeKind::Sell,
+            4.0,
+            price,
+            fees,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            Trade::list_by_account(&pool, acc.get_id())
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+
+        // Removing the buy would leave the sales without units.
+        let id = buy.get_id();
+        assert!(buy.delete(&pool).await.is_err());
+        assert!(Trade::get_by_id(&pool, id).await.is_ok());
+
+        // A sale entered before a buy of the same day is replayed before it.
+        let bond = Security::new(&pool, "BND", "Total Bond Market")
+            .await
+            .unwrap();
+        let buy = Trade::new(
+            &pool,
+            acc.get_id(),
+            bond.get_id(),
+            date(1),
+            TradeKind::Buy,
+            10.0,
+            price,
+            fees,
+        )
+        .await
+        .unwrap();
+        Trade::new(
+            &pool,
+            acc.get_id(),
+            bond.get_id(),
+            date(2),
+            TradeKind::Sell,
+            6.0,
+            price,
+            fees,
+        )
+        .await
+        .unwrap();
+        Trade::new(
+            &pool,
+            acc.get_id(),
+            bond.get_id(),
+            date(2),
+            TradeKind::Buy,
+            6.0,
+            price,
+            fees,
+        )
+        .await
+        .unwrap();
+        assert!(buy.delete(&pool).await.is_err());
+
+        pool.close().await;
+        std::fs::remove_file("investments_position_test.db").unwrap();
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Result, SqlitePool};
+
+use super::{money::Money, scheduled::SCHEDULE_MATCH_DAYS};
+
+#[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct Loan {
+    loan_id: i32,
+    account: i32,
+    principal: Money,
+    annual_rate: f64,
+    term_months: i32,
+    start_date: NaiveDate,
+    payment_account: Option<i32>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Installment {
+    pub number: i32,
+    pub date: NaiveDate,
+    pub payment: Money,
+    pub interest: Money,
+    pub principal: Money,
+    /// Outstanding balance after this installment.
+    pub balance: Money,
+    /// Transaction of the payment account matched to this installment.
+    pub transaction: Option<i32>,
+}
+
+impl Loan {
+    pub async fn new(
+        pool: &SqlitePool,
+        account: i32,
+        principal: Money,
+        annual_rate: f64,
+        term_months: i32,
+        start_date: NaiveDate,
+        payment_account: Option<i32>,
+    ) -> Result<Self> {
+        if !principal.is_positive() || term_months <= 0 || annual_rate < 0.0 {
+            return Err(sqlx::Error::Protocol(String::from(
+                "Loan principal and term must be positive and the rate cannot be negative",
+            )));
+        }
+
+        sqlx::query(concat!(
+            "INSERT INTO loans(account, principal, annual_rate, term_months, start_date, ",
+            "payment_account) VALUES (?,?,?,?,?,?) RETURNING *"
+        ))
+        .bind(account)
+        .bind(principal)
+        .bind(annual_rate)
+        .bind(term_months)
+        .bind(start_date)
+        .bind(payment_account)
+        .fetch_one(pool)
+        .await
+        .and_then(|r| Loan::from_row(&r))
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: i32) -> Result<Self> {
+        sqlx::query("SELECT * FROM loans WHERE loan_id=?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .and_then(|r| Loan::from_row(&r))
+    }
+
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for r in sqlx::query("SELECT * FROM loans")
+            .fetch_all(pool)
+            .await?
+            .iter()
+        {
+            res.push(Loan::from_row(r)?)
+        }
+        Ok(res)
+    }
+
+    pub async fn delete(self, pool: &SqlitePool) -> Result<()> {
+        sqlx::query("DELETE FROM loans WHERE loan_id=?")
+            .bind(self.loan_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub fn get_id(&self) -> i32 {
+        self.loan_id
+    }
+
+    pub fn get_account(&self) -> i32 {
+        self.account
+    }
+
+    pub fn get_principal(&self) -> Money {
+        self.principal
+    }
+
+    pub fn get_payment_account(&self) -> Option<i32> {
+        self.payment_account
+    }
+
+    fn monthly_rate(&self) -> f64 {
+        self.annual_rate / 1200.0
+    }
+
+    /// Fixed monthly payment of a standard amortizing loan.
+    pub fn monthly_payment(&self) -> Money {
+        let principal = self.principal.cents() as f64;
+        let n = self.term_months as f64;
+        let r = self.monthly_rate();
+        let payment = if r == 0.0 {
+            principal / n
+        } else {
+            principal * r / (1.0 - (1.0 + r).powf(-n))
+        };
+        Money::from_cents_f64(payment)
+    }
+
+    /// Installments of the loan. Rounding differences are settled in the last one, so the
+    /// balance ends at exactly zero.
+    pub fn amortization(&self) -> Vec<Installment> {
+        let payment = self.monthly_payment();
+        let r = self.monthly_rate();
+        let mut balance = self.principal;
+        let mut res = Vec::with_capacity(self.term_months as usize);
+
+        for number in 1..=self.term_months {
+            let Some(date) = self
+                .start_date
+                .checked_add_months(Months::new(number as u32 - 1))
+            else {
+                break;
+            };
+            let interest = Money::from_cents_f64(balance.cents() as f64 * r);
+            let principal = if number == self.term_months {
+                balance
+            } else {
+                (payment - interest).min(balance)
+            };
+            balance -= principal;
+            res.push(Installment {
+                number,
+                date,
+                payment: principal + interest,
+                interest,
+                principal,
+                balance,
+                transaction: None,
+            });
+        }
+        res
+    }
+
+    async fn payments(&self, pool: &SqlitePool) -> Result<HashMap<i32, i32>> {
+        let rows: Vec<(i32, i32)> =
+            sqlx::query_as("SELECT installment, transaction_id FROM loan_payments WHERE loan=?")
+                .bind(self.loan_id)
+                .fetch_all(pool)
+                .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Amortization schedule with the payments matched so far.
+    pub async fn schedule(&self, pool: &SqlitePool) -> Result<Vec<Installment>> {
+        let payments = self.payments(pool).await?;
+        let mut schedule = self.amortization();
+        for i in schedule.iter_mut() {
+            i.transaction = payments.get(&i.number).copied();
+        }
+        Ok(schedule)
+    }
+
+    /// Outstanding balance on `date`, according to the schedule.
+    pub fn balance_at(schedule: &[Installment], principal: Money, date: NaiveDate) -> Money {
+        schedule
+            .iter()
+            .take_while(|x| x.date <= date)
+            .last()
+            .map_or(principal, |x| x.balance)
+    }
+
+    /// Links the installments that are still unpaid to the transactions of the payment
+    /// account with the same amount, at most a few days away from their due date.
+    pub async fn match_payments(&self, pool: &SqlitePool) -> Result<usize> {
+        let Some(payment_account) = self.payment_account else {
+            return Ok(0);
+        };
+
+        let mut matched = 0;
+        for installment in self.schedule(pool).await? {
+            if installment.transaction.is_some() {
+                continue;
+            }
+            let date = installment.date;
+            let tx: Option<i32> = sqlx::query_scalar(concat!(
+                "SELECT transaction_id FROM transactions ",
+                "WHERE account=? AND amount=? AND tx_date BETWEEN ? AND ? ",
+                "AND transaction_id NOT IN (SELECT transaction_id FROM loan_payments) ",
+                "ORDER BY ABS(julianday(tx_date) - julianday(?)) LIMIT 1"
+            ))
+            .bind(payment_account)
+            .bind(-installment.payment)
+            .bind(date - Duration::days(SCHEDULE_MATCH_DAYS))
+            .bind(date + Duration::days(SCHEDULE_MATCH_DAYS))
+            .bind(date)
+            .fetch_optional(pool)
+            .await?;
+
+            if let Some(tx) = tx {
+                sqlx::query(
+                    "INSERT INTO loan_payments(loan, installment, transaction_id) VALUES (?,?,?)",
+                )
+                .bind(self.loan_id)
+                .bind(installment.number)
+                .bind(tx)
+                .execute(pool)
+                .await?;
+                matched += 1;
+            }
+        }
+        Ok(matched)
+    }
+
+    /// Matches payments of every loan paid from `account`.
+    pub async fn match_account_payments(pool: &SqlitePool, account: i32) -> Result<usize> {
+        let mut matched = 0;
+        for loan in Self::list(pool).await? {
+            if loan.payment_account == Some(account) {
+                matched += loan.match_payments(pool).await?;
+            }
+        }
+        Ok(matched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Loan;
+    use crate::models::money::Money;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn amortization_test() {
+        let loan = Loan {
+            loan_id: 1,
+            account: 1,
+            principal: Money::from_cents(20000000),
+            annual_rate: 3.0,
+            term_months: 240,
+            start_date: NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(),
+            payment_account: None,
+        };
+
+        assert_eq!(loan.monthly_payment(), Money::from_cents(110920));
+        let schedule = loan.amortization();
+        assert_eq!(schedule.len(), 240);
+        assert_eq!(schedule[0].interest, Money::from_cents(50000));
+        assert_eq!(schedule[0].principal, Money::from_cents(60920));
+        assert_eq!(schedule[0].balance, Money::from_cents(20000000 - 60920));
+        assert_eq!(
+            schedule[1].date,
+            NaiveDate::from_ymd_opt(2023, 2, 28).unwrap()
+        );
+        assert_eq!(schedule.last().unwrap().balance, Money::ZERO);
+        assert_eq!(
+            schedule.iter().map(|x| x.principal).sum::<Money>(),
+            Money::from_cents(20000000)
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, AddAssign, Neg, Sub, SubAssign},
+    str::FromStr,
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Decimals of the minor unit.
+const DECIMALS: usize = 2;
+const SCALE: i64 = 100;
+
+/// An amount of money, stored as a whole number of minor units (cents).
+///
+/// It is parsed from and formatted to decimal strings like `-1234.56` without going
+/// through floating point, so large values are exact.
+///
+/// Amounts are serialized and deserialized as decimal strings. JSON numbers are
+/// rejected: the API used to take integer cents, so reading `5` as either five cents
+/// or five units would silently misread requests written for the other format.
+#[derive(sqlx::Type, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[sqlx(transparent)]
+pub struct Money(i64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    Empty,
+    InvalidCharacter(char),
+    TooManyDecimals,
+    Overflow,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::Empty => write!(f, "empty amount"),
+            MoneyError::InvalidCharacter(c) => write!(f, "invalid character '{c}' in amount"),
+            MoneyError::TooManyDecimals => {
+                write!(f, "amounts cannot have more than {DECIMALS} decimals")
+            }
+            MoneyError::Overflow => write!(f, "amount is too large"),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Money(cents)
+    }
+
+    pub const fn cents(&self) -> i64 {
+        self.0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.0 > 0
+    }
+
+    pub fn abs(&self) -> Self {
+        Money(self.0.abs())
+    }
+
+    /// Rounds a floating point number of cents, for values that are the result of
+    /// estimations such as averages or interests.
+    pub fn from_cents_f64(cents: f64) -> Self {
+        Money(cents.round() as i64)
+    }
+
+    /// Value as a floating point number of units, for charts and ratios.
+    pub fn to_f64(&self) -> f64 {
+        self.0 as f64 / SCALE as f64
+    }
+}
+
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        if int.is_empty() && frac.is_empty() {
+            return Err(MoneyError::Empty);
+        }
+        if frac.len() > DECIMALS {
+            return Err(MoneyError::TooManyDecimals);
+        }
+        if let Some(c) = int
+            .chars()
+            .chain(frac.chars())
+            .find(|c| !c.is_ascii_digit())
+        {
+            return Err(MoneyError::InvalidCharacter(c));
+        }
+
+        let mut cents: i64 = 0;
+        for c in int.chars().chain(frac.chars()) {
+            cents = cents
+                .checked_mul(10)
+                .and_then(|x| x.checked_add(c as i64 - '0' as i64))
+                .ok_or(MoneyError::Overflow)?;
+        }
+        for _ in frac.len()..DECIMALS {
+            cents = cents.checked_mul(10).ok_or(MoneyError::Overflow)?;
+        }
+
+        Ok(Money(if negative { -cents } else { cents }))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        write!(
+            f,
+            "{sign}{}.{:0width$}",
+            abs / SCALE as u64,
+            abs % SCALE as u64,
+            width = DECIMALS
+        )
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+struct MoneyVisitor;
+
+impl<'de> de::Visitor<'de> for MoneyVisitor {
+    type Value = Money;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a decimal amount as a string, like \"12.34\"")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Money, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Money, E> {
+        Err(E::custom(format!(
+            "amounts are decimal strings, integer cents are no longer accepted: send \"{}\" instead of {v}",
+            Money(v)
+        )))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Money, E> {
+        match i64::try_from(v) {
+            Ok(v) => self.visit_i64(v),
+            Err(_) => Err(E::custom(MoneyError::Overflow)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Money) {
+        self.0 += rhs.0
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, rhs: Money) -> Money {
+        Money(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Money) {
+        self.0 -= rhs.0
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        Money(iter.map(|x| x.0).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Money, MoneyError};
+
+    #[test]
+    fn parse_format_test() {
+        assert_eq!("12.34".parse(), Ok(Money::from_cents(1234)));
+        assert_eq!("-0.5".parse(), Ok(Money::from_cents(-50)));
+        assert_eq!("+7".parse(), Ok(Money::from_cents(700)));
+        assert_eq!(".05".parse(), Ok(Money::from_cents(5)));
+        assert_eq!(
+            "92233720368547758.07".parse(),
+            Ok(Money::from_cents(i64::MAX))
+        );
+        assert_eq!(
+            "92233720368547758.08".parse::<Money>(),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!("1.234".parse::<Money>(), Err(MoneyError::TooManyDecimals));
+        assert_eq!(
+            "1,20".parse::<Money>(),
+            Err(MoneyError::InvalidCharacter(','))
+        );
+        assert_eq!("-".parse::<Money>(), Err(MoneyError::Empty));
+
+        assert_eq!(Money::from_cents(-5).to_string(), "-0.05");
+        assert_eq!(Money::from_cents(123456789012).to_string(), "1234567890.12");
+
+        let parsed: Vec<Money> = serde_json::from_str(r#"["30000000.01", "5"]"#).unwrap();
+        assert_eq!(
+            parsed,
+            vec![Money::from_cents(3000000001), Money::from_cents(500)]
+        );
+        assert!(serde_json::from_str::<Money>("1999").is_err());
+        assert!(serde_json::from_str::<Money>("19.99").is_err());
+        assert_eq!(
+            serde_json::to_string(&Money::from_cents(-1999)).unwrap(),
+            r#""-19.99""#
+        );
+    }
+}
@@ -0,0 +1,539 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
+
+use regex::{RegexSet, SetMatches};
+use serde::Serialize;
+use sqlx::{Result, SqlitePool};
+
+use super::{
+    rules::{ConditionField, ConditionOperator, Rule, RuleCondition},
+    transaction::Transaction,
+};
+
+/// A check of a compiled rule. Regexes point to their pattern in one of the sets of the
+/// engine, so they are evaluated once per transaction for all the rules.
+enum Check {
+    Description(usize),
+    Payee(usize),
+    Condition(RuleCondition),
+}
+
+struct CompiledRule {
+    rule: Rule,
+    checks: Vec<Check>,
+}
+
+impl CompiledRule {
+    fn matches(&self, tx: &Transaction, description: &SetMatches, payee: &SetMatches) -> bool {
+        if self.checks.is_empty() {
+            return false;
+        }
+
+        let mut results = self.checks.iter().map(|c| match c {
+            Check::Description(i) => description.matched(*i),
+            Check::Payee(i) => payee.matched(*i),
+            // Only regex conditions can fail, and those are compiled into the sets.
+            Check::Condition(c) => c.matches(tx).unwrap_or(false),
+        });
+        if self.rule.match_all {
+            results.all(|x| x)
+        } else {
+            results.any(|x| x)
+        }
+    }
+}
+
+/// The classification rules compiled once, to evaluate them against many transactions.
+pub struct RuleEngine {
+    rules: Vec<CompiledRule>,
+    description: RegexSet,
+    payee: RegexSet,
+}
+
+impl RuleEngine {
+    /// Compiles the enabled `rules`, which are evaluated in the order they are given.
+    pub fn new(rules: Vec<Rule>) -> std::result::Result<Self, regex::Error> {
+        let mut description = Vec::new();
+        let mut payee = Vec::new();
+        let mut compiled = Vec::with_capacity(rules.len());
+
+        for mut rule in rules.into_iter().filter(|r| r.enabled) {
+            let mut checks = Vec::new();
+            if !rule.regex.is_empty() {
+                description.push(rule.regex.clone());
+                checks.push(Check::Description(description.len() - 1));
+            }
+            for c in std::mem::take(&mut rule.conditions) {
+                checks.push(match (c.field, c.operator) {
+                    (ConditionField::Description, ConditionOperator::Matches) => {
+                        description.push(c.value);
+                        Check::Description(description.len() - 1)
+                    }
+                    (ConditionField::Payee, ConditionOperator::Matches) => {
+                        payee.push(c.value);
+                        Check::Payee(payee.len() - 1)
+                    }
+                    _ => Check::Condition(c),
+                });
+            }
+            compiled.push(CompiledRule { rule, checks });
+        }
+
+        Ok(Self {
+            rules: compiled,
+            description: RegexSet::new(description)?,
+            payee: RegexSet::new(payee)?,
+        })
+    }
+
+    pub async fn load(pool: &SqlitePool) -> Result<Self> {
+        Self::new(Rule::list(pool).await?).map_err(|e| sqlx::Error::Protocol(format!("{e}")))
+    }
+
+    /// First rule that matches the transaction.
+    pub fn classify(&self, tx: &Transaction) -> Option<&Rule> {
+        let description = self.description.matches(tx.get_original_description());
+        let payee = self.payee.matches(tx.get_payee().unwrap_or_default());
+        self.rules
+            .iter()
+            .find(|r| r.matches(tx, &description, &payee))
+            .map(|r| &r.rule)
+    }
+}
+
+/// Transactions a rule would catch, to check a draft before saving it.
+#[derive(Serialize, Debug)]
+pub struct RulePreview {
+    pub matched: usize,
+    pub uncategorized: usize,
+    /// Matched transactions that already have a category other than the one of the rule.
+    pub other_category: usize,
+    /// Newest matched transactions.
+    pub samples: Vec<Transaction>,
+}
+
+impl RulePreview {
+    /// Evaluates `rule` alone against every transaction. The rule does not need to exist
+    /// in the database.
+    pub async fn new(pool: &SqlitePool, rule: Rule, max_samples: usize) -> Result<Self> {
+        let category = rule.category;
+        let engine =
+            RuleEngine::new(vec![rule]).map_err(|e| sqlx::Error::Protocol(format!("{e}")))?;
+
+        let mut preview = RulePreview {
+            matched: 0,
+            uncategorized: 0,
+            other_category: 0,
+            samples: Vec::new(),
+        };
+        for tx in Transaction::list_by_date(pool, None, None, None, None, false).await? {
+            if engine.classify(&tx).is_none() {
+                continue;
+            }
+
+            preview.matched += 1;
+            match tx.get_category() {
+                None => preview.uncategorized += 1,
+                Some(c) if c != category => preview.other_category += 1,
+                Some(_) => {}
+            }
+            if preview.samples.len() < max_samples {
+                preview.samples.push(tx);
+            }
+        }
+        Ok(preview)
+    }
+}
+
+/// Rule engine shared by the requests, built on first use and dropped whenever the rules
+/// change.
+#[derive(Default)]
+pub struct RuleCache {
+    engine: RwLock<Option<Arc<RuleEngine>>>,
+    generation: AtomicU64,
+}
+
+impl RuleCache {
+    pub async fn get(&self, pool: &SqlitePool) -> Result<Arc<RuleEngine>> {
+        if let Some(engine) = self.engine.read().unwrap().as_ref() {
+            return Ok(engine.clone());
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let engine = Arc::new(RuleEngine::load(pool).await?);
+
+        // Rules changed while loading, so the engine is used once but not kept.
+        let mut cached = self.engine.write().unwrap();
+        if self.generation.load(Ordering::SeqCst) == generation {
+            *cached = Some(engine.clone());
+        }
+        Ok(engine)
+    }
+
+    pub fn invalidate(&self) {
+        let mut cached = self.engine.write().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *cached = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RuleCache, RuleEngine, RulePreview};
+    use crate::models::{
+        account::{Account, AccountType, RecategorizeMode},
+        categories::Category,
+        money::Money,
+        rules::{ActionType, ConditionField, ConditionOperator, Rule, RuleAction, RuleCondition},
+        transaction::{CategorySource, Transaction, TransactionStatus},
+    };
+
+    #[tokio::test]
+    async fn classify_account_test() {
+        let pool = crate::create_db("sqlite://rule_engine_test.db")
+            .await
+            .unwrap();
+        let acc = Account::new(&pool, "rule_engine_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let shopping = Category::new(&pool, "Shopping", "").await.unwrap();
+        let streaming = Category::new(&pool, "Streaming", "").await.unwrap();
+
+        Rule::new(&pool, "AMAZON PRIME".to_string(), streaming.category_id)
+            .await
+            .unwrap();
+        Rule::new(&pool, "AMAZON".to_string(), shopping.category_id)
+            .await
+            .unwrap();
+        let by_payee = Rule::new(&pool, String::new(), shopping.category_id)
+            .await
+            .unwrap();
+        RuleCondition::new(
+            &pool,
+            by_payee.rule_id,
+            ConditionField::Payee,
+            ConditionOperator::Matches,
+            "(?i)^ikea",
+        )
+        .await
+        .unwrap();
+
+        let mut ids = Vec::new();
+        for description in ["AMAZON PRIME", "AMAZON MKTPLACE", "CARD 1234", "SALARY"] {
+            let tx = Transaction::new(
+                &pool,
+                acc.get_id(),
+                description,
+                chrono::Utc::now().date_naive(),
+                None,
+                Money::from_cents(-1000),
+                TransactionStatus::Cleared,
+            )
+            .await
+            .unwrap();
+            ids.push(tx.get_id());
+        }
+        Transaction::get_by_id(&pool, ids[2])
+            .await
+            .unwrap()
+            .set_payee(&pool, Some("IKEA Madrid"))
+            .await
+            .unwrap();
+
+        let cache = RuleCache::default();
+        let engine = cache.get(&pool).await.unwrap();
+        assert_eq!(
+            acc.recategorize_transactions(&pool, &engine, RecategorizeMode::Uncategorized)
+                .await
+                .unwrap(),
+            3
+        );
+
+        let mut categories = Vec::new();
+        for id in ids.iter() {
+            categories.push(
+                Transaction::get_by_id(&pool, *id)
+                    .await
+                    .unwrap()
+                    .get_category(),
+            );
+        }
+        assert_eq!(
+            categories,
+            vec![
+                Some(streaming.category_id),
+                Some(shopping.category_id),
+                Some(shopping.category_id),
+                None
+            ]
+        );
+
+        // The cached engine is kept until the rules change.
+        Rule::new(&pool, "SALARY".to_string(), shopping.category_id)
+            .await
+            .unwrap();
+        let salary = Transaction::get_by_id(&pool, ids[3]).await.unwrap();
+        assert!(cache.get(&pool).await.unwrap().classify(&salary).is_none());
+        cache.invalidate();
+        assert!(cache.get(&pool).await.unwrap().classify(&salary).is_some());
+
+        assert!(RuleEngine::new(vec![]).unwrap().classify(&salary).is_none());
+
+        let draft = Rule {
+            rule_id: 0,
+            regex: "AMAZON".to_string(),
+            category: shopping.category_id,
+            priority: 0,
+            match_all: true,
+            enabled: true,
+            conditions: Vec::new(),
+            actions: Vec::new(),
+        };
+        let preview = RulePreview::new(&pool, draft, 1).await.unwrap();
+        assert_eq!(preview.matched, 2);
+        assert_eq!(preview.uncategorized, 0);
+        assert_eq!(preview.other_category, 1);
+        assert_eq!(preview.samples.len(), 1);
+
+        pool.close().await;
+        std::fs::remove_file("rule_engine_test.db").unwrap();
+    }
+
+    #[tokio::test]
+    async fn actions_test() {
+        let pool = crate::create_db("sqlite://rule_engine_actions_test.db")
+            .await
+            .unwrap();
+        let checking = Account::new(&pool, "actions_checking", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let savings = Account::new(&pool, "actions_savings", AccountType::Savings, None)
+            .await
+            .unwrap();
+        let transfers = Category::new(&pool, "Transfers", "").await.unwrap();
+
+        let rule = Rule::new(&pool, "^TRF 0042".to_string(), transfers.category_id)
+            .await
+            .unwrap();
+        let savings_id = savings.get_id().to_string();
+        for (action, value) in [
+            (ActionType::Review, ""),
+            (ActionType::Transfer, savings_id.as_str()),
+            (ActionType::AddTag, "savings"),
+            (ActionType::SetPayee, "Me"),
+            (ActionType::Rename, "Transfer to savings"),
+        ] {
+            RuleAction::new(&pool, rule.rule_id, action, value)
+                .await
+                .unwrap();
+        }
+        assert!(
+            RuleAction::new(&pool, rule.rule_id, ActionType::Transfer, "savings")
+                .await
+                .is_err()
+        );
+
+        // Actions are applied in a fixed order, whatever the order they were added in.
+        let mut rule = Rule::get_by_id(&pool, rule.rule_id).await.unwrap();
+        let order: Vec<ActionType> = rule.actions.iter().map(|a| a.action).collect();
+        assert_eq!(
+            order,
+            vec![
+                ActionType::Rename,
+                ActionType::SetPayee,
+                ActionType::AddTag,
+                ActionType::Transfer,
+                ActionType::Review
+            ]
+        );
+
+        let mut tx = Transaction::new(
+            &pool,
+            checking.get_id(),
+            "TRF 0042 ES12 3456",
+            chrono::Utc::now().date_naive(),
+            None,
+            Money::from_cents(-50000),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert!(tx.recategorize(&pool, &engine).await.unwrap());
+        assert_eq!(tx.get_category(), Some(transfers.category_id));
+        assert_eq!(tx.get_description(), "Transfer to savings");
+        assert_eq!(tx.get_payee(), Some("Me"));
+        assert_eq!(tx.get_transfer_account(), Some(savings.get_id()));
+        assert!(tx.needs_review());
+        assert_eq!(tx.get_tags(&pool).await.unwrap(), vec!["savings"]);
+
+        // Moving to another rule undoes the actions of the previous one.
+        let fees = Category::new(&pool, "Fees", "").await.unwrap();
+        let other = Rule::new(&pool, "^TRF".to_string(), fees.category_id)
+            .await
+            .unwrap();
+        RuleAction::new(&pool, other.rule_id, ActionType::AddTag, "fees")
+            .await
+            .unwrap();
+        rule.set_enabled(&pool, false).await.unwrap();
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert!(tx.recategorize(&pool, &engine).await.unwrap());
+        assert_eq!(tx.get_category(), Some(fees.category_id));
+        assert_eq!(tx.get_description(), "TRF 0042 ES12 3456");
+        assert_eq!(tx.get_payee(), None);
+        assert_eq!(tx.get_transfer_account(), None);
+        assert!(!tx.needs_review());
+        assert_eq!(tx.get_tags(&pool).await.unwrap(), vec!["fees"]);
+        assert!(!tx.recategorize(&pool, &engine).await.unwrap());
+
+        pool.close().await;
+        std::fs::remove_file("rule_engine_actions_test.db").unwrap();
+    }
+
+    #[tokio::test]
+    async fn reapply_test() {
+        let pool = crate::create_db("sqlite://rule_engine_reapply_test.db")
+            .await
+            .unwrap();
+        let acc = Account::new(&pool, "reapply_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let shopping = Category::new(&pool, "Shopping", "").await.unwrap();
+        let books = Category::new(&pool, "Books", "").await.unwrap();
+
+        let mut rule = Rule::new(&pool, "AMAZON".to_string(), shopping.category_id)
+            .await
+            .unwrap();
+        let mut ids = Vec::new();
+        for description in ["AMAZON EU", "AMAZON KINDLE"] {
+            let tx = Transaction::new(
+                &pool,
+                acc.get_id(),
+                description,
+                chrono::Utc::now().date_naive(),
+                None,
+                Money::from_cents(-1500),
+                TransactionStatus::Cleared,
+            )
+            .await
+            .unwrap();
+            ids.push(tx.get_id());
+        }
+
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        let mode = RecategorizeMode::Uncategorized;
+        assert_eq!(
+            acc.recategorize_transactions(&pool, &engine, mode)
+                .await
+                .unwrap(),
+            2
+        );
+        let tx = Transaction::get_by_id(&pool, ids[0]).await.unwrap();
+        assert_eq!(tx.get_category_source(), Some(CategorySource::Rule));
+        assert_eq!(tx.get_category_rule(), Some(rule.rule_id));
+
+        // A category chosen by hand is kept.
+        let mut kindle = Transaction::get_by_id(&pool, ids[1]).await.unwrap();
+        kindle
+            .set_category(&pool, Some(books.category_id))
+            .await
+            .unwrap();
+        assert_eq!(kindle.get_category_source(), Some(CategorySource::Manual));
+
+        rule.set_category(&pool, books.category_id).await.unwrap();
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert_eq!(
+            acc.recategorize_transactions(&pool, &engine, mode)
+                .await
+                .unwrap(),
+            0
+        );
+        let mode = RecategorizeMode::Reapply;
+        assert_eq!(
+            Account::recategorize_all(&pool, &engine, mode)
+                .await
+                .unwrap(),
+            1
+        );
+        let tx = Transaction::get_by_id(&pool, ids[0]).await.unwrap();
+        assert_eq!(tx.get_category(), Some(books.category_id));
+
+        // A transaction renamed by its rule still matches it on the next pass.
+        rule.set_category(&pool, shopping.category_id)
+            .await
+            .unwrap();
+        RuleAction::new(&pool, rule.rule_id, ActionType::Rename, "Amazon")
+            .await
+            .unwrap();
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert_eq!(
+            acc.recategorize_transactions(&pool, &engine, mode)
+                .await
+                .unwrap(),
+            1
+        );
+        let tx = Transaction::get_by_id(&pool, ids[0]).await.unwrap();
+        assert_eq!(tx.get_description(), "Amazon");
+        assert_eq!(tx.get_original_description(), "AMAZON EU");
+        assert_eq!(
+            acc.recategorize_transactions(&pool, &engine, mode)
+                .await
+                .unwrap(),
+            0
+        );
+        let tx = Transaction::get_by_id(&pool, ids[0]).await.unwrap();
+        assert_eq!(tx.get_category(), Some(shopping.category_id));
+        assert_eq!(tx.get_category_rule(), Some(rule.rule_id));
+
+        // Actions added to the rule later are applied once, so later edits are kept.
+        RuleAction::new(&pool, rule.rule_id, ActionType::Review, "")
+            .await
+            .unwrap();
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert_eq!(
+            acc.recategorize_transactions(&pool, &engine, mode)
+                .await
+                .unwrap(),
+            1
+        );
+        let mut tx = Transaction::get_by_id(&pool, ids[0]).await.unwrap();
+        assert!(tx.needs_review());
+        tx.set_needs_review(&pool, false).await.unwrap();
+        assert_eq!(
+            acc.recategorize_transactions(&pool, &engine, mode)
+                .await
+                .unwrap(),
+            0
+        );
+        let tx = Transaction::get_by_id(&pool, ids[0]).await.unwrap();
+        assert!(!tx.needs_review());
+
+        // Actions are not applied to categories chosen by hand either.
+        let mut kindle = Transaction::get_by_id(&pool, ids[1]).await.unwrap();
+        assert!(!kindle.recategorize(&pool, &engine).await.unwrap());
+        assert_eq!(kindle.get_description(), "AMAZON KINDLE");
+        assert!(!kindle.needs_review());
+
+        // Rule categories that no rule matches any more are removed.
+        rule.set_enabled(&pool, false).await.unwrap();
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert_eq!(
+            acc.recategorize_transactions(&pool, &engine, mode)
+                .await
+                .unwrap(),
+            1
+        );
+        let tx = Transaction::get_by_id(&pool, ids[0]).await.unwrap();
+        assert_eq!(tx.get_category(), None);
+        assert_eq!(tx.get_category_source(), None);
+        assert_eq!(tx.get_description(), "AMAZON EU");
+        let kindle = Transaction::get_by_id(&pool, ids[1]).await.unwrap();
+        assert_eq!(kindle.get_category(), Some(books.category_id));
+
+        pool.close().await;
+        std::fs::remove_file("rule_engine_reapply_test.db").unwrap();
+    }
+}
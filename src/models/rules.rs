@@ -1,54 +1,761 @@
+use std::{collections::HashMap, str::FromStr};
+
+use chrono::Datelike;
 use regex::Regex;
-use serde::Serialize;
-use sqlx::{FromRow, SqlitePool};
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, Executor, FromRow, Sqlite, SqlitePool};
+
+use super::{
+    money::Money,
+    transaction::{MoveDirection, Transaction},
+};
+
+/// Property of a transaction a condition looks at.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionField {
+    Description,
+    Payee,
+    Amount,
+    /// `income` for positive amounts, `expense` for negative ones.
+    Sign,
+    /// Id of the account.
+    Account,
+    /// Day of the month of the booking date.
+    DayOfMonth,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionOperator {
+    /// The value is a regex.
+    Matches,
+    Equals,
+    GreaterThan,
+    LessThan,
+    /// The value is an inclusive range like `25..5`. A range whose start is greater than
+    /// its end wraps around, so `25..5` covers the turn of the month.
+    Between,
+}
+
+/// Change a rule makes to the transactions it matches, besides setting the category.
+/// The actions of a rule are applied in the order of this enum.
+#[derive(
+    sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ActionType {
+    /// Replaces the description with the value.
+    Rename,
+    /// Sets the payee to the value.
+    SetPayee,
+    /// Adds the value as a tag.
+    AddTag,
+    /// Marks the transaction as a transfer with the account whose id is the value.
+    Transfer,
+    /// Flags the transaction to be checked by hand. The value is not used.
+    Review,
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct RuleAction {
+    pub action_id: i32,
+    pub rule: i32,
+    pub action: ActionType,
+    pub value: String,
+}
+
+impl RuleAction {
+    pub async fn new(
+        conn: impl Acquire<'_, Database = Sqlite>,
+        rule: i32,
+        action: ActionType,
+        value: &str,
+    ) -> sqlx::Result<Self> {
+        Self::validate(action, value).map_err(sqlx::Error::Protocol)?;
+        let mut db_tx = conn.begin().await?;
+        let res = sqlx::query(concat!(
+            "INSERT INTO rule_actions(rule, action, value) ",
+            "VALUES (?,?,?) RETURNING *"
+        ))
+        .bind(rule)
+        .bind(action)
+        .bind(value.trim())
+        .fetch_one(&mut *db_tx)
+        .await
+        .and_then(|r| RuleAction::from_row(&r))?;
+        db_tx.commit().await?;
+        Ok(res)
+    }
+
+    pub async fn get_by_id(
+        conn: impl Executor<'_, Database = Sqlite>,
+        action_id: i32,
+    ) -> sqlx::Result<Self> {
+        sqlx::query("SELECT * FROM rule_actions WHERE action_id=?")
+            .bind(action_id)
+            .fetch_one(conn)
+            .await
+            .and_then(|r| RuleAction::from_row(&r))
+    }
+
+    pub async fn delete(self, conn: impl Executor<'_, Database = Sqlite>) -> sqlx::Result<()> {
+        sqlx::query("DELETE FROM rule_actions WHERE action_id=?")
+            .bind(self.action_id)
+            .execute(conn)
+            .await
+            .map(|_| ())
+    }
+
+    pub fn validate(action: ActionType, value: &str) -> Result<(), String> {
+        match action {
+            ActionType::Rename | ActionType::AddTag if value.trim().is_empty() => {
+                Err(format!("{action:?} needs a value"))
+            }
+            ActionType::Transfer if value.trim().parse::<i32>().is_err() => {
+                Err(format!("{value:?} is not an account id"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Account a transfer action points to.
+    pub fn transfer_account(&self) -> Option<i32> {
+        match self.action {
+            ActionType::Transfer => self.value.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct RuleCondition {
+    pub condition_id: i32,
+    pub rule: i32,
+    pub field: ConditionField,
+    pub operator: ConditionOperator,
+    pub value: String,
+}
+
+fn compare<T: PartialOrd + FromStr>(actual: T, operator: ConditionOperator, value: &str) -> bool {
+    let parse = |s: &str| s.trim().parse::<T>().ok();
+    match operator {
+        ConditionOperator::Equals => parse(value).is_some_and(|v| actual == v),
+        ConditionOperator::GreaterThan => parse(value).is_some_and(|v| actual > v),
+        ConditionOperator::LessThan => parse(value).is_some_and(|v| actual < v),
+        ConditionOperator::Between => {
+            let Some((start, end)) = value
+                .split_once("..")
+                .and_then(|(s, e)| Some((parse(s)?, parse(e)?)))
+            else {
+                return false;
+            };
+            if start <= end {
+                start <= actual && actual <= end
+            } else {
+                actual >= start || actual <= end
+            }
+        }
+        ConditionOperator::Matches => false,
+    }
+}
+
+fn check_value<T: FromStr>(operator: ConditionOperator, value: &str) -> bool {
+    let parses = |s: &str| s.trim().parse::<T>().is_ok();
+    match operator {
+        ConditionOperator::Between => value
+            .split_once("..")
+            .is_some_and(|(s, e)| parses(s) && parses(e)),
+        _ => parses(value),
+    }
+}
+
+impl RuleCondition {
+    pub async fn new(
+        conn: impl Acquire<'_, Database = Sqlite>,
+        rule: i32,
+        field: ConditionField,
+        operator: ConditionOperator,
+        value: &str,
+    ) -> sqlx::Result<Self> {
+        Self::validate(field, operator, value).map_err(sqlx::Error::Protocol)?;
+        // The insert only completes once the statement is finished, which the commit
+        // guarantees before the rule is read again from another connection.
+        let mut db_tx = conn.begin().await?;
+        let condition = sqlx::query(concat!(
+            "INSERT INTO rule_conditions(rule, field, operator, value) ",
+            "VALUES (?,?,?,?) RETURNING *"
+        ))
+        .bind(rule)
+        .bind(field)
+        .bind(operator)
+        .bind(value)
+        .fetch_one(&mut *db_tx)
+        .await
+        .and_then(|r| RuleCondition::from_row(&r))?;
+        db_tx.commit().await?;
+        Ok(condition)
+    }
+
+    pub async fn get_by_id(
+        conn: impl Executor<'_, Database = Sqlite>,
+        condition_id: i32,
+    ) -> sqlx::Result<Self> {
+        sqlx::query("SELECT * FROM rule_conditions WHERE condition_id=?")
+            .bind(condition_id)
+            .fetch_one(conn)
+            .await
+            .and_then(|r| RuleCondition::from_row(&r))
+    }
+
+    pub async fn delete(self, conn: impl Executor<'_, Database = Sqlite>) -> sqlx::Result<()> {
+        sqlx::query("DELETE FROM rule_conditions WHERE condition_id=?")
+            .bind(self.condition_id)
+            .execute(conn)
+            .await
+            .map(|_| ())
+    }
+
+    /// Checks that the operator applies to the field and that the value can be read.
+    pub fn validate(
+        field: ConditionField,
+        operator: ConditionOperator,
+        value: &str,
+    ) -> Result<(), String> {
+        let valid = match (field, operator) {
+            (ConditionField::Description | ConditionField::Payee, ConditionOperator::Matches) => {
+                Regex::new(value).map_err(|e| format!("{e}"))?;
+                true
+            }
+            (ConditionField::Description | ConditionField::Payee, ConditionOperator::Equals) => {
+                true
+            }
+            (ConditionField::Description | ConditionField::Payee, _) => {
+                return Err(format!("{field:?} only supports matches and equals"));
+            }
+            (ConditionField::Sign, ConditionOperator::Equals) => {
+                value == "income" || value == "expense"
+            }
+            (ConditionField::Account, ConditionOperator::Equals) => value.parse::<i32>().is_ok(),
+            (ConditionField::Sign | ConditionField::Account, _) => {
+                return Err(format!("{field:?} only supports equals"));
+            }
+            (_, ConditionOperator::Matches) => {
+                return Err(format!("{field:?} does not support matches"));
+            }
+            (ConditionField::Amount, _) => check_value::<Money>(operator, value),
+            (ConditionField::DayOfMonth, _) => check_value::<u32>(operator, value),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("Invalid value '{value}' for {field:?}"))
+        }
+    }
+
+    pub fn matches(&self, tx: &Transaction) -> Result<bool, regex::Error> {
+        let text = match self.field {
+            ConditionField::Description => tx.get_original_description(),
+            ConditionField::Payee => tx.get_payee().unwrap_or_default(),
+            ConditionField::Amount => {
+                return Ok(compare(tx.get_amount(), self.operator, &self.value))
+            }
+            ConditionField::Sign => {
+                return Ok(match self.value.as_str() {
+                    "income" => tx.get_amount().is_positive(),
+                    "expense" => tx.get_amount().is_negative(),
+                    _ => false,
+                })
+            }
+            ConditionField::Account => {
+                return Ok(compare(tx.get_account(), self.operator, &self.value))
+            }
+            ConditionField::DayOfMonth => {
+                return Ok(compare(tx.get_date().day(), self.operator, &self.value))
+            }
+        };
+
+        match self.operator {
+            ConditionOperator::Matches => Ok(Regex::new(&self.value)?.is_match(text)),
+            _ => Ok(text == self.value),
+        }
+    }
+}
 
 #[derive(FromRow, Serialize)]
 pub struct Rule {
     pub rule_id: i32,
+    /// Pattern the description has to match. An empty regex leaves the description out.
     pub regex: String,
     pub category: i32,
+    /// Position in the evaluation order, lower values are tried first.
+    pub priority: i32,
+    /// Whether the regex and every condition have to match, or just one of them.
+    pub match_all: bool,
+    /// Disabled rules are kept but left out of the classification.
+    pub enabled: bool,
+    #[sqlx(skip)]
+    pub conditions: Vec<RuleCondition>,
+    /// Applied along with the category, sorted in the order they take effect.
+    #[sqlx(skip)]
+    pub actions: Vec<RuleAction>,
 }
 
 impl Rule {
-    pub async fn get_by_id(pool: &SqlitePool, rule_id: i32) -> sqlx::Result<Self> {
-        sqlx::query("SELECT * FROM rules WHERE rule_id=?")
+    pub async fn get_by_id(
+        conn: impl Acquire<'_, Database = Sqlite>,
+        rule_id: i32,
+    ) -> sqlx::Result<Self> {
+        // Read in a single transaction, so the conditions and actions belong to the same
+        // version of the rule.
+        let mut db_tx = conn.begin().await?;
+        let mut rule = sqlx::query("SELECT * FROM rules WHERE rule_id=?")
             .bind(rule_id)
-            .fetch_one(pool)
+            .fetch_one(&mut *db_tx)
             .await
-            .and_then(|r| Rule::from_row(&r))
+            .and_then(|r| Rule::from_row(&r))?;
+        rule.conditions =
+            sqlx::query_as("SELECT * FROM rule_conditions WHERE rule=? ORDER BY condition_id")
+                .bind(rule_id)
+                .fetch_all(&mut *db_tx)
+                .await?;
+        rule.actions = sqlx::query_as("SELECT * FROM rule_actions WHERE rule=? ORDER BY action_id")
+            .bind(rule_id)
+            .fetch_all(&mut *db_tx)
+            .await?;
+        db_tx.commit().await?;
+        rule.actions.sort_by_key(|a| a.action);
+        Ok(rule)
     }
 
-    pub async fn new(pool: &SqlitePool, regex: String, category: i32) -> sqlx::Result<Self> {
-        sqlx::query("INSERT INTO rules(regex, category) VALUES (?,?) RETURNING *")
-            .bind(regex)
-            .bind(category)
-            .fetch_one(pool)
-            .await
-            .and_then(|r| Rule::from_row(&r))
+    /// Creates a rule evaluated after all the existing ones.
+    pub async fn new(
+        conn: impl Acquire<'_, Database = Sqlite>,
+        regex: String,
+        category: i32,
+    ) -> sqlx::Result<Self> {
+        Self::check_regex(&regex)?;
+        let mut db_tx = conn.begin().await?;
+        let rule = sqlx::query(concat!(
+            "INSERT INTO rules(regex, category, priority) ",
+            "VALUES (?,?,(SELECT COALESCE(MAX(priority), 0)+1 FROM rules)) RETURNING *"
+        ))
+        .bind(regex)
+        .bind(category)
+        .fetch_one(&mut *db_tx)
+        .await
+        .and_then(|r| Rule::from_row(&r))?;
+        db_tx.commit().await?;
+        Ok(rule)
     }
 
+    /// Lists the rules in evaluation order, with their conditions.
     pub async fn list(pool: &SqlitePool) -> sqlx::Result<Vec<Self>> {
+        let mut conditions: HashMap<i32, Vec<RuleCondition>> = HashMap::new();
+        for c in sqlx::query_as::<_, RuleCondition>(
+            "SELECT * FROM rule_conditions ORDER BY condition_id",
+        )
+        .fetch_all(pool)
+        .await?
+        {
+            conditions.entry(c.rule).or_default().push(c);
+        }
+
+        let mut actions: HashMap<i32, Vec<RuleAction>> = HashMap::new();
+        for a in sqlx::query_as::<_, RuleAction>("SELECT * FROM rule_actions ORDER BY action_id")
+            .fetch_all(pool)
+            .await?
+        {
+            actions.entry(a.rule).or_default().push(a);
+        }
+
         let mut res = Vec::new();
-        for r in sqlx::query("SELECT * FROM rules")
+        for r in sqlx::query("SELECT * FROM rules ORDER BY priority, rule_id")
             .fetch_all(pool)
             .await?
             .iter()
         {
-            res.push(Rule::from_row(r)?)
+            let mut rule = Rule::from_row(r)?;
+            rule.conditions = conditions.remove(&rule.rule_id).unwrap_or_default();
+            rule.actions = actions.remove(&rule.rule_id).unwrap_or_default();
+            rule.actions.sort_by_key(|a| a.action);
+            res.push(rule)
         }
         Ok(res)
     }
 
-    pub async fn delete(&self, pool: &SqlitePool) -> sqlx::Result<()> {
+    pub async fn set_match_all(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        match_all: bool,
+    ) -> sqlx::Result<()> {
+        sqlx::query("UPDATE rules SET match_all=? WHERE rule_id=?")
+            .bind(match_all)
+            .bind(self.rule_id)
+            .execute(conn)
+            .await?;
+        self.match_all = match_all;
+        Ok(())
+    }
+
+    fn check_regex(regex: &str) -> sqlx::Result<()> {
+        Regex::new(regex)
+            .map(|_| ())
+            .map_err(|e| sqlx::Error::Protocol(format!("{e}")))
+    }
+
+    pub async fn set_regex(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        regex: String,
+    ) -> sqlx::Result<()> {
+        Self::check_regex(&regex)?;
+        sqlx::query("UPDATE rules SET regex=? WHERE rule_id=?")
+            .bind(&regex)
+            .bind(self.rule_id)
+            .execute(conn)
+            .await?;
+        self.regex = regex;
+        Ok(())
+    }
+
+    pub async fn set_category(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        category: i32,
+    ) -> sqlx::Result<()> {
+        sqlx::query("UPDATE rules SET category=? WHERE rule_id=?")
+            .bind(category)
+            .bind(self.rule_id)
+            .execute(conn)
+            .await?;
+        self.category = category;
+        Ok(())
+    }
+
+    pub async fn set_enabled(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        enabled: bool,
+    ) -> sqlx::Result<()> {
+        sqlx::query("UPDATE rules SET enabled=? WHERE rule_id=?")
+            .bind(enabled)
+            .bind(self.rule_id)
+            .execute(conn)
+            .await?;
+        self.enabled = enabled;
+        Ok(())
+    }
+
+    /// Deletes the rule along with its conditions.
+    pub async fn delete(self, conn: impl Executor<'_, Database = Sqlite>) -> sqlx::Result<()> {
         sqlx::query("DELETE FROM rules WHERE rule_id=?")
             .bind(self.rule_id)
-            .execute(pool)
+            .execute(conn)
             .await
             .map(|_| ())
     }
 
-    pub fn matches(&self, description: &str) -> Result<bool, regex::Error> {
-        let re = Regex::new(&self.regex)?;
-        Ok(re.is_match(description))
+    /// Swaps the rule with its neighbour in the evaluation order. `Up` makes it be tried
+    /// earlier. Returns `false` when it is already the first (or last) one.
+    pub async fn move_in_order(
+        &mut self,
+        conn: impl Acquire<'_, Database = Sqlite>,
+        direction: MoveDirection,
+    ) -> sqlx::Result<bool> {
+        let mut db_tx = conn.begin().await?;
+
+        let neighbour: Option<(i32, i32)> = sqlx::query_as(match direction {
+            MoveDirection::Up => concat!(
+                "SELECT rule_id, priority FROM rules WHERE priority<? ",
+                "ORDER BY priority DESC LIMIT 1"
+            ),
+            MoveDirection::Down => concat!(
+                "SELECT rule_id, priority FROM rules WHERE priority>? ",
+                "ORDER BY priority ASC LIMIT 1"
+            ),
+        })
+        .bind(self.priority)
+        .fetch_optional(&mut *db_tx)
+        .await?;
+
+        let (neighbour_id, neighbour_priority) = match neighbour {
+            Some(n) => n,
+            None => return Ok(false),
+        };
+
+        sqlx::query("UPDATE rules SET priority=? WHERE rule_id=?")
+            .bind(self.priority)
+            .bind(neighbour_id)
+            .execute(&mut *db_tx)
+            .await?;
+        sqlx::query("UPDATE rules SET priority=? WHERE rule_id=?")
+            .bind(neighbour_priority)
+            .bind(self.rule_id)
+            .execute(&mut *db_tx)
+            .await?;
+
+        db_tx.commit().await?;
+        self.priority = neighbour_priority;
+        Ok(true)
+    }
+
+    /// Evaluates the regex against the original description along with the conditions. A rule
+    /// without any of them matches nothing. Whether the rule is enabled is not checked.
+    pub fn matches(&self, tx: &Transaction) -> Result<bool, regex::Error> {
+        let mut results = Vec::with_capacity(self.conditions.len() + 1);
+        if !self.regex.is_empty() {
+            results.push(Regex::new(&self.regex)?.is_match(tx.get_original_description()));
+        }
+        for c in self.conditions.iter() {
+            results.push(c.matches(tx)?);
+        }
+
+        Ok(if results.is_empty() {
+            false
+        } else if self.match_all {
+            results.into_iter().all(|x| x)
+        } else {
+            results.into_iter().any(|x| x)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConditionField, ConditionOperator, Rule, RuleCondition};
+    use crate::models::{
+        account::{Account, AccountType},
+        audit::{AuditAction, AuditEntry},
+        categories::Category,
+        money::Money,
+        rule_engine::RuleEngine,
+        transaction::{MoveDirection, Transaction, TransactionStatus},
+    };
+    use chrono::NaiveDate;
+
+    #[tokio::test]
+    async fn priority_test() {
+        let pool = crate::create_db("sqlite://rules_priority_test.db")
+            .await
+            .unwrap();
+        let acc = Account::new(&pool, "rules_priority_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let shopping = Category::new(&pool, "Shopping", "").await.unwrap();
+        let streaming = Category::new(&pool, "Streaming", "").await.unwrap();
+
+        Rule::new(&pool, "AMAZON".to_string(), shopping.category_id)
+            .await
+            .unwrap();
+        let mut prime = Rule::new(&pool, "AMAZON PRIME".to_string(), streaming.category_id)
+            .await
+            .unwrap();
+
+        let mut tx = Transaction::new(
+            &pool,
+            acc.get_id(),
+            "AMAZON PRIME MEMBERSHIP",
+            chrono::Utc::now().date_naive(),
+            None,
+            Money::from_cents(-499),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert!(tx.recategorize(&pool, &engine).await.unwrap());
+        assert_eq!(tx.get_category(), Some(shopping.category_id));
+
+        assert!(!prime
+            .move_in_order(&pool, MoveDirection::Down)
+            .await
+            .unwrap());
+        assert!(prime.move_in_order(&pool, MoveDirection::Up).await.unwrap());
+        let rules = Rule::list(&pool).await.unwrap();
+        assert_eq!(rules[0].rule_id, prime.rule_id);
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert!(tx.recategorize(&pool, &engine).await.unwrap());
+        assert_eq!(tx.get_category(), Some(streaming.category_id));
+
+        pool.close().await;
+        std::fs::remove_file("rules_priority_test.db").unwrap();
+    }
+    #[tokio::test]
+    async fn conditions_test() {
+        let pool = crate::create_db("sqlite://rules_conditions_test.db")
+            .await
+            .unwrap();
+        let checking = Account::new(&pool, "conditions_checking", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let savings = Account::new(&pool, "conditions_savings", AccountType::Savings, None)
+            .await
+            .unwrap();
+        let gifts = Category::new(&pool, "Gifts received", "").await.unwrap();
+
+        let rule = Rule::new(&pool, "BIZUM".to_string(), gifts.category_id)
+            .await
+            .unwrap();
+        let account = checking.get_id().to_string();
+        for (field, operator, value) in [
+            (ConditionField::Sign, ConditionOperator::Equals, "income"),
+            (ConditionField::Account, ConditionOperator::Equals, &account),
+            (
+                ConditionField::DayOfMonth,
+                ConditionOperator::Between,
+                "25..5",
+            ),
+        ] {
+            RuleCondition::new(&pool, rule.rule_id, field, operator, value)
+                .await
+                .unwrap();
+        }
+        assert!(RuleCondition::new(
+            &pool,
+            rule.rule_id,
+            ConditionField::Amount,
+            ConditionOperator::Between,
+            "10..abc"
+        )
+        .await
+        .is_err());
+
+        let mut rule = Rule::get_by_id(&pool, rule.rule_id).await.unwrap();
+        assert_eq!(rule.conditions.len(), 3);
+
+        let date = |d| NaiveDate::from_ymd_opt(2023, 12, d).unwrap();
+        let cases = [
+            (checking.get_id(), date(1), 2500, true),
+            (checking.get_id(), date(28), 2500, true),
+            (checking.get_id(), date(15), 2500, false),
+            (checking.get_id(), date(1), -2500, false),
+            (savings.get_id(), date(1), 2500, false),
+        ];
+        for (account, day, amount, expected) in cases {
+            let tx = Transaction::new(
+                &pool,
+                account,
+                "BIZUM FROM ANA",
+                day,
+                None,
+                Money::from_cents(amount),
+                TransactionStatus::Cleared,
+            )
+            .await
+            .unwrap();
+            assert_eq!(rule.matches(&tx).unwrap(), expected);
+        }
+
+        let tx = Transaction::new(
+            &pool,
+            savings.get_id(),
+            "TRANSFER",
+            date(15),
+            None,
+            Money::from_cents(-100),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+        assert!(!rule.matches(&tx).unwrap());
+        rule.set_match_all(&pool, false).await.unwrap();
+        assert!(!rule.matches(&tx).unwrap());
+        let tx = Transaction::new(
+            &pool,
+            checking.get_id(),
+            "TRANSFER",
+            date(15),
+            None,
+            Money::from_cents(-100),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+        assert!(rule.matches(&tx).unwrap());
+
+        pool.close().await;
+        std::fs::remove_file("rules_conditions_test.db").unwrap();
+    }
+
+    #[tokio::test]
+    async fn manage_test() {
+        let pool = crate::create_db("sqlite://rules_manage_test.db")
+            .await
+            .unwrap();
+        let acc = Account::new(&pool, "rules_manage_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let groceries = Category::new(&pool, "Groceries", "").await.unwrap();
+        let eating_out = Category::new(&pool, "Eating out", "").await.unwrap();
+
+        assert!(Rule::new(&pool, "(".to_string(), groceries.category_id)
+            .await
+            .is_err());
+        let mut rule = Rule::new(&pool, "MERCADONA".to_string(), groceries.category_id)
+            .await
+            .unwrap();
+        assert!(rule.enabled);
+        let condition = RuleCondition::new(
+            &pool,
+            rule.rule_id,
+            ConditionField::Sign,
+            ConditionOperator::Equals,
+            "expense",
+        )
+        .await
+        .unwrap();
+
+        let tx = Transaction::new(
+            &pool,
+            acc.get_id(),
+            "RESTAURANTE LA TASCA",
+            NaiveDate::from_ymd_opt(2023, 11, 10).unwrap(),
+            None,
+            Money::from_cents(-3550),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+
+        assert!(rule.set_regex(&pool, "[".to_string()).await.is_err());
+        rule.set_regex(&pool, "RESTAURANTE".to_string())
+            .await
+            .unwrap();
+        rule.set_category(&pool, eating_out.category_id)
+            .await
+            .unwrap();
+        let mut rule = Rule::get_by_id(&pool, rule.rule_id).await.unwrap();
+        assert_eq!(rule.regex, "RESTAURANTE");
+        assert_eq!(rule.category, eating_out.category_id);
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert_eq!(engine.classify(&tx).map(|r| r.rule_id), Some(rule.rule_id));
+
+        // Disabled rules are listed but not applied.
+        rule.set_enabled(&pool, false).await.unwrap();
+        assert!(!Rule::list(&pool).await.unwrap()[0].enabled);
+        let engine = RuleEngine::load(&pool).await.unwrap();
+        assert!(engine.classify(&tx).is_none());
+
+        rule.delete(&pool).await.unwrap();
+        assert!(Rule::list(&pool).await.unwrap().is_empty());
+        assert!(RuleCondition::get_by_id(&pool, condition.condition_id)
+            .await
+            .is_err());
+
+        // Conditions removed along with their rule are logged as well.
+        let history = AuditEntry::list_for_row(&pool, "rule_conditions", condition.condition_id)
+            .await
+            .unwrap();
+        let actions: Vec<AuditAction> = history.iter().map(|x| x.get_action()).collect();
+        assert_eq!(actions, vec![AuditAction::Create, AuditAction::Delete]);
+
+        pool.close().await;
+        std::fs::remove_file("rules_manage_test.db").unwrap();
     }
 }
@@ -0,0 +1,478 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, FromRow, Result, Sqlite, SqlitePool};
+
+use super::{
+    money::Money,
+    transaction::{NewTransaction, Transaction, TransactionStatus},
+};
+
+/// Days a statement entry may differ from the expected date of a scheduled
+/// transaction and still be matched with it.
+pub const SCHEDULE_MATCH_DAYS: i64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Subset of an iCalendar RRULE: `FREQ=MONTHLY;BYMONTHDAY=1`, `FREQ=WEEKLY;INTERVAL=2`...
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub by_month_day: Option<u32>,
+}
+
+#[derive(Debug)]
+pub struct ScheduleError(String);
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid schedule: {}", self.0)
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_month_day = None;
+
+        for part in s.split(';').map(str::trim).filter(|x| !x.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| ScheduleError(part.to_string()))?;
+            let invalid = || ScheduleError(part.to_string());
+            match key.to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value.parse().map_err(|_| invalid())?;
+                    if interval == 0 {
+                        return Err(invalid());
+                    }
+                }
+                "BYMONTHDAY" => {
+                    let day: u32 = value.parse().map_err(|_| invalid())?;
+                    if !(1..=31).contains(&day) {
+                        return Err(invalid());
+                    }
+                    by_month_day = Some(day);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(Schedule {
+            frequency: frequency.ok_or_else(|| ScheduleError(format!("{s} has no FREQ")))?,
+            interval,
+            by_month_day,
+        })
+    }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = ScheduleError;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let freq = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={freq}")?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if let Some(day) = self.by_month_day {
+            write!(f, ";BYMONTHDAY={day}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(value: Schedule) -> Self {
+        value.to_string()
+    }
+}
+
+fn with_month_day(date: NaiveDate, day: u32) -> NaiveDate {
+    // Days past the end of the month fall on its last day.
+    (1..=day)
+        .rev()
+        .find_map(|d| date.with_day(d))
+        .unwrap_or(date)
+}
+
+impl Schedule {
+    /// Ties monthly and yearly schedules to the day of month of their first occurrence, so
+    /// a shorter month does not move every later occurrence.
+    pub fn anchored(mut self, start: NaiveDate) -> Self {
+        if self.by_month_day.is_none()
+            && matches!(self.frequency, Frequency::Monthly | Frequency::Yearly)
+        {
+            self.by_month_day = Some(start.day());
+        }
+        self
+    }
+
+    pub fn next_after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let months = match self.frequency {
+            Frequency::Daily => {
+                return date.checked_add_signed(Duration::days(self.interval as i64))
+            }
+            Frequency::Weekly => {
+                return date.checked_add_signed(Duration::weeks(self.interval as i64))
+            }
+            Frequency::Monthly => self.interval,
+            Frequency::Yearly => self.interval * 12,
+        };
+        let next = date.with_day(1)?.checked_add_months(Months::new(months))?;
+        Some(with_month_day(
+            next,
+            self.by_month_day.unwrap_or(date.day()),
+        ))
+    }
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug)]
+pub struct ScheduledTransaction {
+    scheduled_id: i32,
+    account: i32,
+    description: String,
+    category: Option<i32>,
+    amount: Money,
+    #[sqlx(try_from = "String")]
+    schedule: Schedule,
+    next_date: NaiveDate,
+    end_date: Option<NaiveDate>,
+}
+
+impl ScheduledTransaction {
+    pub async fn new(
+        pool: &SqlitePool,
+        account: i32,
+        description: &str,
+        category: Option<i32>,
+        amount: Money,
+        schedule: Schedule,
+        start: NaiveDate,
+    ) -> Result<Self> {
+        sqlx::query(concat!(
+            "INSERT INTO scheduled_transactions(",
+            "account, description, category, amount, schedule, next_date",
+            ") VALUES (?,?,?,?,?,?) RETURNING *"
+        ))
+        .bind(account)
+        .bind(description)
+        .bind(category)
+        .bind(amount)
+        .bind(schedule.anchored(start).to_string())
+        .bind(start)
+        .fetch_one(pool)
+        .await
+        .and_then(|r| ScheduledTransaction::from_row(&r))
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: i32) -> Result<Self> {
+        sqlx::query("SELECT * FROM scheduled_transactions WHERE scheduled_id=?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .and_then(|r| ScheduledTransaction::from_row(&r))
+    }
+
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for r in sqlx::query("SELECT * FROM scheduled_transactions ORDER BY next_date")
+            .fetch_all(pool)
+            .await?
+            .iter()
+        {
+            res.push(ScheduledTransaction::from_row(r)?)
+        }
+        Ok(res)
+    }
+
+    pub async fn list_by_account(pool: &SqlitePool, account: i32) -> Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for r in
+            sqlx::query("SELECT * FROM scheduled_transactions WHERE account=? ORDER BY next_date")
+                .bind(account)
+                .fetch_all(pool)
+                .await?
+                .iter()
+        {
+            res.push(ScheduledTransaction::from_row(r)?)
+        }
+        Ok(res)
+    }
+
+    pub async fn delete(self, conn: impl Executor<'_, Database = Sqlite>) -> Result<()> {
+        sqlx::query("DELETE FROM scheduled_transactions WHERE scheduled_id=?")
+            .bind(self.scheduled_id)
+            .execute(conn)
+            .await
+            .map(|_| ())
+    }
+
+    pub fn get_id(&self) -> i32 {
+        self.scheduled_id
+    }
+
+    pub fn get_account(&self) -> i32 {
+        self.account
+    }
+
+    pub fn get_description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn get_category(&self) -> Option<i32> {
+        self.category
+    }
+
+    pub fn get_amount(&self) -> Money {
+        self.amount
+    }
+
+    pub fn get_schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    pub fn get_next_date(&self) -> NaiveDate {
+        self.next_date
+    }
+
+    pub fn get_end_date(&self) -> Option<NaiveDate> {
+        self.end_date
+    }
+
+    pub async fn set_end_date(&mut self, pool: &SqlitePool, end: Option<NaiveDate>) -> Result<()> {
+        sqlx::query("UPDATE scheduled_transactions SET end_date=? WHERE scheduled_id=?")
+            .bind(end)
+            .bind(self.scheduled_id)
+            .execute(pool)
+            .await?;
+        self.end_date = end;
+        Ok(())
+    }
+
+    /// Occurrences from the next pending one up to `until`, both included.
+    pub fn occurrences_until(&self, until: NaiveDate) -> Vec<NaiveDate> {
+        let until = self.end_date.map_or(until, |end| end.min(until));
+        let mut res = Vec::new();
+        let mut date = Some(self.next_date);
+        while let Some(d) = date.filter(|d| *d <= until) {
+            res.push(d);
+            date = self.schedule.next_after(d);
+        }
+        res
+    }
+
+    /// Creates the transactions that are due on or before `today` as pending, and moves
+    /// the schedule to its next occurrence. Returns the number of transactions created.
+    pub async fn create_due(&mut self, pool: &SqlitePool, today: NaiveDate) -> Result<usize> {
+        let due = self.occurrences_until(today);
+        let mut db_tx = pool.begin().await?;
+        let mut created = 0;
+        for date in due.iter() {
+            let exists: Option<i32> = sqlx::query_scalar(
+                "SELECT transaction_id FROM transactions WHERE scheduled=? AND tx_date=?",
+            )
+            .bind(self.scheduled_id)
+            .bind(date)
+            .fetch_optional(&mut *db_tx)
+            .await?;
+            if exists.is_some() {
+                continue;
+            }
+
+            Transaction::insert(
+                &mut *db_tx,
+                &NewTransaction {
+                    account: self.account,
+                    description: &self.description,
+                    date: *date,
+                    category: self.category,
+                    amount: self.amount,
+                    status: TransactionStatus::Pending,
+                    payee: None,
+                    value_date: None,
+                    timestamp: None,
+                    scheduled: Some(self.scheduled_id),
+                },
+            )
+            .await?;
+            created += 1;
+        }
+
+        // Past its end date a schedule keeps a next_date after end_date, which leaves it
+        // without occurrences.
+        let next_date = due
+            .last()
+            .map(|last| self.schedule.next_after(*last).unwrap_or(*last));
+        if let Some(next_date) = next_date {
+            sqlx::query("UPDATE scheduled_transactions SET next_date=? WHERE scheduled_id=?")
+                .bind(next_date)
+                .bind(self.scheduled_id)
+                .execute(&mut *db_tx)
+                .await?;
+        }
+        db_tx.commit().await?;
+
+        if let Some(next_date) = next_date {
+            self.next_date = next_date;
+        }
+        Ok(created)
+    }
+
+    pub async fn create_all_due(pool: &SqlitePool, today: NaiveDate) -> Result<usize> {
+        let mut created = 0;
+        for mut s in Self::list(pool).await? {
+            created += s.create_due(pool, today).await?;
+        }
+        Ok(created)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Frequency, Schedule, ScheduledTransaction, SCHEDULE_MATCH_DAYS};
+    use crate::models::{
+        account::{Account, AccountType},
+        money::Money,
+        transaction::{NewTransaction, Transaction, TransactionStatus},
+    };
+    use chrono::NaiveDate;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn parse_test() {
+        let s: Schedule = "FREQ=WEEKLY;INTERVAL=2".parse().unwrap();
+        assert_eq!(s.frequency, Frequency::Weekly);
+        assert_eq!(s.interval, 2);
+        assert_eq!(s.to_string(), "FREQ=WEEKLY;INTERVAL=2");
+
+        assert!("FREQ=HOURLY".parse::<Schedule>().is_err());
+        assert!("INTERVAL=2".parse::<Schedule>().is_err());
+        assert!("FREQ=MONTHLY;BYMONTHDAY=32".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn next_after_test() {
+        let biweekly: Schedule = "FREQ=WEEKLY;INTERVAL=2".parse().unwrap();
+        assert_eq!(
+            biweekly.next_after(date(2023, 12, 25)),
+            Some(date(2024, 1, 8))
+        );
+
+        let monthly = "FREQ=MONTHLY"
+            .parse::<Schedule>()
+            .unwrap()
+            .anchored(date(2024, 1, 31));
+        assert_eq!(
+            monthly.next_after(date(2024, 1, 31)),
+            Some(date(2024, 2, 29))
+        );
+        assert_eq!(
+            monthly.next_after(date(2024, 2, 29)),
+            Some(date(2024, 3, 31))
+        );
+
+        let yearly: Schedule = "FREQ=YEARLY;BYMONTHDAY=15".parse().unwrap();
+        assert_eq!(
+            yearly.next_after(date(2023, 6, 15)),
+            Some(date(2024, 6, 15))
+        );
+    }
+
+    #[tokio::test]
+    async fn create_due_test() {
+        let pool = crate::create_db("sqlite://scheduled_test.db")
+            .await
+            .unwrap();
+        let acc = Account::new(&pool, "scheduled_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let mut rent = ScheduledTransaction::new(
+            &pool,
+            acc.get_id(),
+            "Rent",
+            None,
+            Money::from_cents(-80000),
+            "FREQ=MONTHLY;BYMONTHDAY=1".parse().unwrap(),
+            date(2023, 10, 1),
+        )
+        .await
+        .unwrap();
+
+        // An occurrence already created is not counted again.
+        Transaction::insert(
+            &pool,
+            &NewTransaction {
+                account: acc.get_id(),
+                description: "Rent",
+                date: date(2023, 11, 1),
+                category: None,
+                amount: Money::from_cents(-80000),
+                status: TransactionStatus::Pending,
+                payee: None,
+                value_date: None,
+                timestamp: None,
+                scheduled: Some(rent.scheduled_id),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(rent.create_due(&pool, date(2023, 12, 15)).await.unwrap(), 2);
+        assert_eq!(rent.get_next_date(), date(2024, 1, 1));
+        assert_eq!(rent.create_due(&pool, date(2023, 12, 15)).await.unwrap(), 0);
+
+        let pending = Transaction::find_scheduled_match(
+            &pool,
+            acc.get_id(),
+            date(2023, 12, 2),
+            Money::from_cents(-80000),
+            SCHEDULE_MATCH_DAYS,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(pending.get_status(), TransactionStatus::Pending);
+        assert_eq!(pending.get_scheduled(), Some(rent.get_id()));
+        assert_eq!(pending.get_date(), date(2023, 12, 1));
+
+        pool.close().await;
+        std::fs::remove_file("scheduled_test.db").unwrap();
+    }
+}
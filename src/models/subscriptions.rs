@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Months, NaiveDate};
+use serde::Serialize;
+use sqlx::{Result, SqlitePool};
+
+use super::{money::Money, transaction::Transaction};
+
+/// Occurrences needed before a series of charges is reported.
+const MIN_OCCURRENCES: usize = 3;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Period {
+    /// Classifies the typical number of days between two charges.
+    fn from_gap(days: i64) -> Option<Self> {
+        match days {
+            5..=9 => Some(Period::Weekly),
+            26..=35 => Some(Period::Monthly),
+            350..=380 => Some(Period::Yearly),
+            _ => None,
+        }
+    }
+
+    /// Days a charge may be late and still count as part of the series.
+    fn tolerance(&self) -> i64 {
+        match self {
+            Period::Weekly => 2,
+            Period::Monthly => 5,
+            Period::Yearly => 10,
+        }
+    }
+
+    fn matches_gap(&self, days: i64) -> bool {
+        let nominal = match self {
+            Period::Weekly => 7,
+            Period::Monthly => 30,
+            Period::Yearly => 365,
+        };
+        // Months are between 28 and 31 days long, so they get some extra slack.
+        (days - nominal).abs() <= self.tolerance() + if *self == Period::Monthly { 2 } else { 0 }
+    }
+
+    fn next_after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Period::Weekly => date.checked_add_signed(Duration::weeks(1)),
+            Period::Monthly => date.checked_add_months(Months::new(1)),
+            Period::Yearly => date.checked_add_months(Months::new(12)),
+        }
+    }
+}
+
+/// A charge as seen by the detector.
+#[derive(Debug, Clone)]
+pub struct SeriesEntry {
+    pub account: i32,
+    pub description: String,
+    pub date: NaiveDate,
+    pub amount: Money,
+}
+
+impl From<&Transaction> for SeriesEntry {
+    fn from(tx: &Transaction) -> Self {
+        SeriesEntry {
+            account: tx.get_account(),
+            description: tx.get_description().to_string(),
+            date: tx.get_date(),
+            amount: tx.get_amount(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PriceChange {
+    pub date: NaiveDate,
+    pub old_amount: Money,
+    pub new_amount: Money,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Subscription {
+    pub account: i32,
+    pub description: String,
+    pub period: Period,
+    pub amount: Money,
+    pub occurrences: usize,
+    pub first_seen: NaiveDate,
+    pub last_seen: NaiveDate,
+    pub next_expected: Option<NaiveDate>,
+    pub price_changes: Vec<PriceChange>,
+    /// False when the expected charge is overdue, which usually means it was cancelled.
+    pub active: bool,
+}
+
+/// Drops the parts of a description that change between charges of the same series, such
+/// as dates or reference numbers.
+fn normalize(description: &str) -> String {
+    description
+        .to_lowercase()
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn median(values: &mut [i64]) -> i64 {
+    values.sort_unstable();
+    values[values.len() / 2]
+}
+
+fn detect_series(entries: &[&SeriesEntry], today: NaiveDate) -> Option<Subscription> {
+    if entries.len() < MIN_OCCURRENCES {
+        return None;
+    }
+
+    let mut gaps: Vec<i64> = entries
+        .windows(2)
+        .map(|w| (w[1].date - w[0].date).num_days())
+        .collect();
+    let period = Period::from_gap(median(&mut gaps))?;
+    // Allow a single missed or duplicated charge.
+    if gaps.iter().filter(|x| !period.matches_gap(**x)).count() > 1 {
+        return None;
+    }
+
+    let mut amounts: Vec<i64> = entries.iter().map(|x| x.amount.cents()).collect();
+    let typical = median(&mut amounts).abs();
+    if entries
+        .iter()
+        .any(|x| (x.amount.cents().abs() - typical).abs() * 2 > typical)
+    {
+        return None;
+    }
+
+    let price_changes = entries
+        .windows(2)
+        .filter(|w| w[0].amount != w[1].amount)
+        .map(|w| PriceChange {
+            date: w[1].date,
+            old_amount: w[0].amount,
+            new_amount: w[1].amount,
+        })
+        .collect();
+
+    let first = entries.first()?;
+    let last = entries.last()?;
+    let next_expected = period.next_after(last.date);
+    let active =
+        next_expected.is_some_and(|next| today <= next + Duration::days(period.tolerance()));
+
+    Some(Subscription {
+        account: last.account,
+        description: last.description.clone(),
+        period,
+        amount: last.amount,
+        occurrences: entries.len(),
+        first_seen: first.date,
+        last_seen: last.date,
+        next_expected,
+        price_changes,
+        active,
+    })
+}
+
+impl Subscription {
+    /// Finds series of charges with a similar description and amount that repeat weekly,
+    /// monthly or yearly.
+    pub fn detect(entries: &[SeriesEntry], today: NaiveDate) -> Vec<Subscription> {
+        let mut groups: HashMap<(i32, String), Vec<&SeriesEntry>> = HashMap::new();
+        for e in entries.iter().filter(|x| x.amount.is_negative()) {
+            let key = normalize(&e.description);
+            if !key.is_empty() {
+                groups.entry((e.account, key)).or_default().push(e);
+            }
+        }
+
+        let mut res: Vec<Subscription> = groups
+            .into_values()
+            .filter_map(|mut series| {
+                series.sort_by_key(|x| x.date);
+                detect_series(&series, today)
+            })
+            .collect();
+        res.sort_by(|a, b| {
+            b.active
+                .cmp(&a.active)
+                .then(a.amount.cmp(&b.amount))
+                .then(a.description.cmp(&b.description))
+        });
+        res
+    }
+
+    pub async fn list(
+        pool: &SqlitePool,
+        account: Option<i32>,
+        today: NaiveDate,
+    ) -> Result<Vec<Subscription>> {
+        let txs = Transaction::list_by_date(pool, account, None, None, None, true).await?;
+        let entries: Vec<SeriesEntry> = txs.iter().map(SeriesEntry::from).collect();
+        Ok(Self::detect(&entries, today))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Period, SeriesEntry, Subscription};
+    use crate::models::money::Money;
+    use chrono::NaiveDate;
+
+    fn entry(description: &str, date: (i32, u32, u32), amount: i64) -> SeriesEntry {
+        SeriesEntry {
+            account: 1,
+            description: description.to_string(),
+            date: NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+            amount: Money::from_cents(amount),
+        }
+    }
+
+    #[test]
+    fn detect_test() {
+        let entries = vec![
+            entry("NETFLIX.COM 0123", (2023, 6, 3), -1099),
+            entry("NETFLIX.COM 0456", (2023, 7, 3), -1099),
+            entry("NETFLIX.COM 0789", (2023, 8, 4), -1299),
+            entry("NETFLIX.COM 1011", (2023, 9, 3), -1299),
+            entry("GYM 2023/06", (2023, 6, 1), -3000),
+            entry("GYM 2023/07", (2023, 7, 1), -3000),
+            entry("GYM 2023/08", (2023, 8, 1), -3000),
+            entry("SUPERMARKET", (2023, 6, 10), -5230),
+            entry("SUPERMARKET", (2023, 6, 12), -1200),
+            entry("SUPERMARKET", (2023, 8, 20), -800),
+        ];
+        let today = NaiveDate::from_ymd_opt(2023, 9, 20).unwrap();
+        let subs = Subscription::detect(&entries, today);
+        assert_eq!(subs.len(), 2);
+
+        let netflix = &subs[0];
+        assert!(netflix.active);
+        assert_eq!(netflix.period, Period::Monthly);
+        assert_eq!(netflix.amount, Money::from_cents(-1299));
+        assert_eq!(netflix.next_expected, NaiveDate::from_ymd_opt(2023, 10, 3));
+        assert_eq!(netflix.price_changes.len(), 1);
+        assert_eq!(
+            netflix.price_changes[0].old_amount,
+            Money::from_cents(-1099)
+        );
+
+        let gym = &subs[1];
+        assert!(!gym.active);
+        assert_eq!(gym.occurrences, 3);
+    }
+}
@@ -1,24 +1,101 @@
+use std::collections::HashSet;
+
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, Result, Sqlite, SqlitePool};
+use sqlx::{Acquire, Executor, FromRow, Result, Sqlite, SqliteConnection, SqlitePool};
+
+use crate::models::{
+    money::Money,
+    rule_engine::RuleEngine,
+    rules::{ActionType, Rule, RuleAction},
+};
 
-use crate::models::rules::Rule;
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    /// Entered by hand, not yet seen in a bank statement.
+    Pending,
+    /// Confirmed by the bank.
+    #[default]
+    Cleared,
+    /// Checked against a statement. Reconciled transactions cannot be modified until
+    /// their status is changed back.
+    Reconciled,
+}
+
+/// Who set the category of a transaction.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CategorySource {
+    /// Chosen by hand, rules never change it.
+    Manual,
+    /// Assigned by a rule, it is updated when the rules are applied again.
+    Rule,
+}
 
 #[derive(FromRow, Serialize, Deserialize, Debug)]
 pub struct Transaction {
     transaction_id: i32,
     account: i32,
     description: String,
-    tx_date: DateTime<Utc>,
+    /// Description as imported, when a rule has renamed the transaction.
+    original_description: Option<String>,
+    /// Booking date, the day the transaction appears in the statement.
+    tx_date: NaiveDate,
     category: Option<i32>,
-    amount: i32,
-    accumulated: i32,
+    category_source: Option<CategorySource>,
+    /// Rule that set the category, while it exists.
+    category_rule: Option<i32>,
+    amount: Money,
+    accumulated: Money,
+    tx_order: i32,
+    status: TransactionStatus,
+    scheduled: Option<i32>,
+    /// Day the money is actually available or withdrawn, when the bank reports it.
+    value_date: Option<NaiveDate>,
+    /// Name of the counterpart, when the statement reports it apart from the description.
+    payee: Option<String>,
+    /// Exact time of the operation, when known. It is informative only: ordering and
+    /// reports are based on the booking date.
+    tx_timestamp: Option<DateTime<Utc>>,
+    /// Own account on the other side, when the transaction moves money between accounts.
+    transfer_account: Option<i32>,
+    /// Flagged to be checked by hand.
+    needs_review: bool,
+}
+
+/// Direction in which a transaction moves among the ones booked on the same day. `Up`
+/// places it earlier in the statement, `Down` later.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveDirection {
+    Up,
+    Down,
 }
 
 #[derive(FromRow, Serialize, Deserialize, Debug)]
 pub struct TransactionAggregated {
-    tx_date: DateTime<Utc>,
-    accumulated: i32,
+    tx_date: NaiveDate,
+    accumulated: Money,
+}
+
+/// Every field a transaction can be created with, so that they are stored by a single
+/// statement even when the transaction is created already reconciled.
+#[derive(Debug, Clone)]
+pub struct NewTransaction<'a> {
+    pub account: i32,
+    pub description: &'a str,
+    pub date: NaiveDate,
+    pub category: Option<i32>,
+    pub amount: Money,
+    pub status: TransactionStatus,
+    pub payee: Option<&'a str>,
+    pub value_date: Option<NaiveDate>,
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Schedule the transaction comes from.
+    pub scheduled: Option<i32>,
 }
 
 impl Transaction {
@@ -26,38 +103,77 @@ impl Transaction {
         pool: &SqlitePool,
         account: i32,
         desc: &str,
-        ts: &DateTime<Utc>,
+        date: NaiveDate,
         category: Option<i32>,
-        amount: i32,
+        amount: Money,
+        status: TransactionStatus,
     ) -> Result<Self> {
-        sqlx::query(concat!(
+        Self::insert(
+            pool,
+            &NewTransaction {
+                account,
+                description: desc,
+                date,
+                category,
+                amount,
+                status,
+                payee: None,
+                value_date: None,
+                timestamp: None,
+                scheduled: None,
+            },
+        )
+        .await
+    }
+
+    pub async fn insert(
+        conn: impl Acquire<'_, Database = Sqlite>,
+        new: &NewTransaction<'_>,
+    ) -> Result<Self> {
+        // RETURNING reports the row before the insert trigger has filled in its order and
+        // running balance, so it is read again once the statement is done.
+        let mut db_tx = conn.begin().await?;
+        let id: i32 = sqlx::query_scalar(concat!(
             "INSERT INTO transactions(",
-            "account, description, tx_date, category, amount",
-            ") VALUES (?,?,?,?,?) RETURNING *"
+            "account, description, tx_date, category, category_source, amount, status, ",
+            "payee, value_date, tx_timestamp, scheduled",
+            ") VALUES (?,?,?,?,?,?,?,?,?,?,?) RETURNING transaction_id"
         ))
-        .bind(account)
-        .bind(desc)
-        .bind(ts)
-        .bind(category)
-        .bind(amount)
-        .fetch_one(pool)
-        .await
-        .and_then(|x| Transaction::from_row(&x))
+        .bind(new.account)
+        .bind(new.description)
+        .bind(new.date)
+        .bind(new.category)
+        .bind(new.category.map(|_| CategorySource::Manual))
+        .bind(new.amount)
+        .bind(new.status)
+        .bind(new.payee)
+        .bind(new.value_date)
+        .bind(new.timestamp)
+        .bind(new.scheduled)
+        .fetch_one(&mut *db_tx)
+        .await?;
+        let tx = sqlx::query("SELECT * FROM transactions WHERE transaction_id=?")
+            .bind(id)
+            .fetch_one(&mut *db_tx)
+            .await
+            .and_then(|x| Transaction::from_row(&x))?;
+        db_tx.commit().await?;
+        Ok(tx)
     }
 
-    pub async fn get_by_id(pool: &SqlitePool, tx_id: i32) -> Result<Self> {
+    pub async fn get_by_id(conn: impl Executor<'_, Database = Sqlite>, tx_id: i32) -> Result<Self> {
         sqlx::query("SELECT * FROM transactions WHERE transaction_id=?")
             .bind(tx_id)
-            .fetch_one(pool)
+            .fetch_one(conn)
             .await
             .and_then(|x| Transaction::from_row(&x))
     }
 
     pub async fn list(pool: &SqlitePool, limit: i32, offset: i32, asc: bool) -> Result<Vec<Self>> {
         let rows = sqlx::query(if asc {
-            "SELECT * FROM transactions ORDER BY tx_date ASC LIMIT ? OFFSET ?"
+            "SELECT * FROM transactions ORDER BY tx_date ASC, tx_order ASC LIMIT ? OFFSET ?"
         } else {
-            "SELECT * FROM transactions ORDER BY tx_date DESC LIMIT ? OFFSET ?"
+            "SELECT * FROM transactions ORDER BY tx_date DESC, tx_order DESC LIMIT ? OFFSET ?"
         })
         .bind(limit)
         .bind(offset)
@@ -79,9 +195,15 @@ impl Transaction {
         asc: bool,
     ) -> Result<Vec<Self>> {
         let rows = sqlx::query(if asc {
-            "SELECT * FROM transactions WHERE account=? ORDER BY tx_date ASC LIMIT ? OFFSET ?"
+            concat!(
+                "SELECT * FROM transactions WHERE account=? ",
+                "ORDER BY tx_date ASC, tx_order ASC LIMIT ? OFFSET ?"
+            )
         } else {
-            "SELECT * FROM transactions WHERE account=? ORDER BY tx_date DESC LIMIT ? OFFSET ?"
+            concat!(
+                "SELECT * FROM transactions WHERE account=? ",
+                "ORDER BY tx_date DESC, tx_order DESC LIMIT ? OFFSET ?"
+            )
         })
         .bind(account)
         .bind(limit)
@@ -98,8 +220,8 @@ impl Transaction {
 
     pub fn query_by_date<'a>(
         account: Option<i32>,
-        after: Option<DateTime<Utc>>,
-        before: Option<DateTime<Utc>>,
+        after: Option<NaiveDate>,
+        before: Option<NaiveDate>,
         limit: Option<i32>,
         asc: bool,
     ) -> sqlx::QueryBuilder<'a, Sqlite> {
@@ -121,9 +243,9 @@ impl Transaction {
         }
 
         if asc {
-            query.push(" ORDER BY tx_date ASC");
+            query.push(" ORDER BY tx_date ASC, tx_order ASC");
         } else {
-            query.push(" ORDER BY tx_date DESC");
+            query.push(" ORDER BY tx_date DESC, tx_order DESC");
         }
 
         if let Some(lim) = limit {
@@ -137,8 +259,8 @@ impl Transaction {
     pub async fn list_by_date(
         pool: &SqlitePool,
         account: Option<i32>,
-        after: Option<DateTime<Utc>>,
-        before: Option<DateTime<Utc>>,
+        after: Option<NaiveDate>,
+        before: Option<NaiveDate>,
         limit: Option<i32>,
         asc: bool,
     ) -> Result<Vec<Self>> {
@@ -153,12 +275,36 @@ impl Transaction {
         Ok(res)
     }
 
-    pub async fn list_uncategorized(pool: &SqlitePool, account: i32) -> Result<Vec<Self>> {
+    pub async fn list_uncategorized(
+        conn: impl Executor<'_, Database = Sqlite>,
+        account: i32,
+    ) -> Result<Vec<Self>> {
         let mut query = sqlx::QueryBuilder::new("SELECT * FROM TRANSACTIONS WHERE account=");
         query.push_bind(account);
 
-        query.push(" AND category IS NULL");
-        let rows = query.build().fetch_all(pool).await?;
+        query.push(" AND category IS NULL AND status<>'reconciled'");
+        let rows = query.build().fetch_all(conn).await?;
+
+        let mut ret = Vec::new();
+        for r in &rows {
+            ret.push(Transaction::from_row(r)?);
+        }
+        Ok(ret)
+    }
+
+    /// Transactions the rules may change: uncategorized ones and those categorized by a
+    /// rule, leaving out the reconciled ones.
+    pub async fn list_rule_managed(
+        conn: impl Executor<'_, Database = Sqlite>,
+        account: i32,
+    ) -> Result<Vec<Self>> {
+        let rows = sqlx::query(concat!(
+            "SELECT * FROM transactions WHERE account=? ",
+            "AND (category IS NULL OR category_source='rule') AND status<>'reconciled'"
+        ))
+        .bind(account)
+        .fetch_all(conn)
+        .await?;
 
         let mut ret = Vec::new();
         for r in &rows {
@@ -170,12 +316,15 @@ impl Transaction {
     pub async fn group_by_date(
         pool: &SqlitePool,
         account: i32,
-        after: Option<DateTime<Utc>>,
-        before: Option<DateTime<Utc>>,
+        after: Option<NaiveDate>,
+        before: Option<NaiveDate>,
         asc: bool,
     ) -> Result<Vec<TransactionAggregated>> {
-        let mut query =
-            sqlx::QueryBuilder::new("SELECT accumulated, tx_date FROM transactions WHERE account=");
+        // With a single MAX() aggregate, SQLite takes the bare columns from the row holding
+        // the maximum, so each day reports the balance after its last transaction.
+        let mut query = sqlx::QueryBuilder::new(
+            "SELECT accumulated, tx_date, MAX(tx_order) FROM transactions WHERE account=",
+        );
         query.push_bind(account);
 
         if let Some(a) = after {
@@ -188,7 +337,7 @@ impl Transaction {
             query.push_bind(b);
         }
 
-        query.push(" GROUP BY tx_date HAVING max(tx_order)");
+        query.push(" GROUP BY tx_date");
 
         let rows = query.build().fetch_all(pool).await?;
 
@@ -212,103 +361,1015 @@ impl Transaction {
         &self.description
     }
 
-    pub fn get_timestamp(&self) -> &DateTime<Utc> {
-        &self.tx_date
+    /// Description the rules are evaluated against: the one imported, even if a rule has
+    /// renamed the transaction since.
+    pub fn get_original_description(&self) -> &str {
+        self.original_description
+            .as_deref()
+            .unwrap_or(&self.description)
+    }
+
+    pub fn get_payee(&self) -> Option<&str> {
+        self.payee.as_deref()
+    }
+
+    pub fn get_date(&self) -> NaiveDate {
+        self.tx_date
+    }
+
+    pub fn get_value_date(&self) -> Option<NaiveDate> {
+        self.value_date
+    }
+
+    pub fn get_timestamp(&self) -> Option<&DateTime<Utc>> {
+        self.tx_timestamp.as_ref()
     }
 
     pub fn get_category(&self) -> Option<i32> {
         self.category
     }
 
-    pub async fn set_category(
+    pub fn get_status(&self) -> TransactionStatus {
+        self.status
+    }
+
+    pub fn get_transfer_account(&self) -> Option<i32> {
+        self.transfer_account
+    }
+
+    pub async fn set_transfer_account(
         &mut self,
         pool: &SqlitePool,
-        new_category: Option<i32>,
+        account: Option<i32>,
+    ) -> Result<()> {
+        self.check_unlocked()?;
+        sqlx::query("UPDATE transactions SET transfer_account=? WHERE transaction_id=?")
+            .bind(account)
+            .bind(self.transaction_id)
+            .execute(pool)
+            .await?;
+        self.transfer_account = account;
+        Ok(())
+    }
+
+    pub fn needs_review(&self) -> bool {
+        self.needs_review
+    }
+
+    pub async fn set_needs_review(&mut self, pool: &SqlitePool, needs_review: bool) -> Result<()> {
+        sqlx::query("UPDATE transactions SET needs_review=? WHERE transaction_id=?")
+            .bind(needs_review)
+            .bind(self.transaction_id)
+            .execute(pool)
+            .await?;
+        self.needs_review = needs_review;
+        Ok(())
+    }
+
+    pub async fn get_tags(&self, pool: &SqlitePool) -> Result<Vec<String>> {
+        sqlx::query_scalar("SELECT tag FROM transaction_tags WHERE transaction_id=? ORDER BY tag")
+            .bind(self.transaction_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Replaces the tags of the transaction. Blank tags are dropped.
+    pub async fn set_tags(&mut self, pool: &SqlitePool, tags: &[&str]) -> Result<()> {
+        self.check_unlocked()?;
+        let mut db_tx = pool.begin().await?;
+        sqlx::query("DELETE FROM transaction_tags WHERE transaction_id=?")
+            .bind(self.transaction_id)
+            .execute(&mut *db_tx)
+            .await?;
+        for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            sqlx::query("INSERT OR IGNORE INTO transaction_tags(transaction_id, tag) VALUES (?,?)")
+                .bind(self.transaction_id)
+                .bind(tag)
+                .execute(&mut *db_tx)
+                .await?;
+        }
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.status == TransactionStatus::Reconciled
+    }
+
+    fn check_unlocked(&self) -> Result<()> {
+        if self.is_locked() {
+            Err(sqlx::Error::Protocol(format!(
+                "Transaction {} is reconciled",
+                self.transaction_id
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn set_status(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        status: TransactionStatus,
     ) -> Result<()> {
-        sqlx::query("UPDATE transactions SET category=? WHERE transaction_id=?")
-            .bind(new_category)
+        sqlx::query("UPDATE transactions SET status=? WHERE transaction_id=?")
+            .bind(status)
+            .bind(self.transaction_id)
+            .execute(conn)
+            .await?;
+        self.status = status;
+        Ok(())
+    }
+
+    pub fn get_scheduled(&self) -> Option<i32> {
+        self.scheduled
+    }
+
+    pub async fn set_scheduled(&mut self, pool: &SqlitePool, scheduled: Option<i32>) -> Result<()> {
+        sqlx::query("UPDATE transactions SET scheduled=? WHERE transaction_id=?")
+            .bind(scheduled)
             .bind(self.transaction_id)
             .execute(pool)
             .await?;
+        self.scheduled = scheduled;
+        Ok(())
+    }
+
+    /// Looks for a pending transaction generated from a schedule that an imported statement
+    /// entry confirms: same account and amount, at most `max_days` away from `date`.
+    pub async fn find_scheduled_match(
+        conn: impl Executor<'_, Database = Sqlite>,
+        account: i32,
+        date: NaiveDate,
+        amount: Money,
+        max_days: i64,
+    ) -> Result<Option<Self>> {
+        let row = sqlx::query(concat!(
+            "SELECT * FROM transactions ",
+            "WHERE account=? AND amount=? AND status=? AND scheduled IS NOT NULL ",
+            "AND tx_date BETWEEN ? AND ? ",
+            "ORDER BY ABS(julianday(tx_date) - julianday(?)) LIMIT 1"
+        ))
+        .bind(account)
+        .bind(amount)
+        .bind(TransactionStatus::Pending)
+        .bind(date - chrono::Duration::days(max_days))
+        .bind(date + chrono::Duration::days(max_days))
+        .bind(date)
+        .fetch_optional(conn)
+        .await?;
+
+        row.map(|r| Transaction::from_row(&r)).transpose()
+    }
+
+    pub fn get_category_source(&self) -> Option<CategorySource> {
+        self.category_source
+    }
+
+    pub fn get_category_rule(&self) -> Option<i32> {
+        self.category_rule
+    }
+
+    /// Sets the category by hand, so the rules will not change it afterwards.
+    pub async fn set_category(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        new_category: Option<i32>,
+    ) -> Result<()> {
+        self.check_unlocked()?;
+        let source = new_category.map(|_| CategorySource::Manual);
+        sqlx::query(concat!(
+            "UPDATE transactions SET category=?, category_source=?, category_rule=NULL ",
+            "WHERE transaction_id=?"
+        ))
+        .bind(new_category)
+        .bind(source)
+        .bind(self.transaction_id)
+        .execute(conn)
+        .await?;
         self.category = new_category;
+        self.category_source = source;
+        self.category_rule = None;
         Ok(())
     }
 
-    pub async fn recategorize(&mut self, pool: &SqlitePool, rules: &Vec<Rule>) -> Result<bool> {
-        for r in rules.iter() {
-            if r.matches(&self.description)
-                .map_err(|_| sqlx::Error::Protocol("RegexError".to_string()))?
-            {
-                self.set_category(pool, Some(r.category)).await?;
-                return Ok(true);
+    /// Applies the first rule of `engine` that matches. Returns whether the transaction
+    /// changed.
+    pub async fn recategorize(&mut self, pool: &SqlitePool, engine: &RuleEngine) -> Result<bool> {
+        self.check_unlocked()?;
+        let Some(rule) = engine.classify(self) else {
+            return Ok(false);
+        };
+
+        let changed = Self::apply_rules(pool, &[(self.transaction_id, Some(rule))]).await?;
+        *self = Self::get_by_id(pool, self.transaction_id).await?;
+        Ok(changed > 0)
+    }
+
+    /// Applies the category and the actions of rules to many transactions, given as
+    /// `(transaction_id, rule)`, in a single database transaction. `None` removes a category
+    /// set by a rule. Reconciled transactions and manual categories are left untouched,
+    /// actions included.
+    ///
+    /// Actions already applied by the rule are not applied again, so later edits are kept.
+    /// Those of a rule the transaction no longer matches, or removed from its rule, are
+    /// undone, restoring the values they replaced. Returns the number of transactions
+    /// changed.
+    pub async fn apply_rules(
+        conn: impl Acquire<'_, Database = Sqlite>,
+        matches: &[(i32, Option<&Rule>)],
+    ) -> Result<usize> {
+        // Four bound parameters per row, well below the SQLite limit.
+        const CHUNK: usize = 200;
+        const RULE_MANAGED: &str =
+            "status<>'reconciled' AND (category IS NULL OR category_source IS NOT 'manual')";
+
+        let mut db_tx = conn.begin().await?;
+        let mut managed = HashSet::new();
+        let mut changed = HashSet::new();
+        for chunk in matches.chunks(CHUNK) {
+            let mut query =
+                sqlx::QueryBuilder::new("SELECT transaction_id FROM transactions WHERE ");
+            query.push(RULE_MANAGED).push(" AND transaction_id IN (");
+            let mut ids = query.separated(",");
+            for (tx_id, _) in chunk {
+                ids.push_bind(*tx_id);
+            }
+            query.push(")");
+            let ids = query
+                .build_query_scalar::<i32>()
+                .fetch_all(&mut *db_tx)
+                .await?;
+            managed.extend(ids);
+
+            let mut query = sqlx::QueryBuilder::new(concat!(
+                "UPDATE transactions SET category=v.column2, category_source=v.column3, ",
+                "category_rule=v.column4 FROM ("
+            ));
+            query.push_values(chunk, |mut row, (tx_id, rule)| {
+                row.push_bind(*tx_id)
+                    .push_bind(rule.map(|r| r.category))
+                    .push_bind(rule.map(|_| CategorySource::Rule))
+                    .push_bind(rule.map(|r| r.rule_id));
+            });
+            query
+                .push(") AS v WHERE transactions.transaction_id=v.column1 AND ")
+                .push(RULE_MANAGED)
+                .push(concat!(
+                    " AND (category IS NOT v.column2 OR category_source IS NOT v.column3 ",
+                    "OR category_rule IS NOT v.column4) RETURNING transaction_id"
+                ));
+            let ids = query
+                .build_query_scalar::<i32>()
+                .fetch_all(&mut *db_tx)
+                .await?;
+            changed.extend(ids);
+        }
+
+        for (tx_id, rule) in matches.iter().filter(|(id, _)| managed.contains(id)) {
+            let actions = rule.map(|r| r.actions.as_slice()).unwrap_or_default();
+            if Self::sync_actions(&mut db_tx, *tx_id, actions).await? {
+                changed.insert(*tx_id);
+            }
+        }
+        db_tx.commit().await?;
+        Ok(changed.len())
+    }
+
+    /// Leaves on the transaction the effects of `actions` only, undoing the rest in reverse
+    /// order. Returns whether any action was applied or undone.
+    async fn sync_actions(
+        conn: &mut SqliteConnection,
+        tx_id: i32,
+        actions: &[RuleAction],
+    ) -> Result<bool> {
+        let applied: Vec<(i32, ActionType)> = sqlx::query_as(concat!(
+            "SELECT action_id, action FROM transaction_rule_actions ",
+            "WHERE transaction_id=? ORDER BY rowid DESC"
+        ))
+        .bind(tx_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut changed = false;
+        for (action_id, action) in applied.iter() {
+            if !actions.iter().any(|a| a.action_id == *action_id) {
+                Self::undo_action(conn, tx_id, *action_id, *action).await?;
+                changed = true;
             }
         }
-        Ok(false)
+        for action in actions.iter() {
+            if !applied.iter().any(|(id, _)| *id == action.action_id) {
+                Self::apply_action(conn, tx_id, action).await?;
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+
+    async fn apply_action(
+        conn: &mut SqliteConnection,
+        tx_id: i32,
+        action: &RuleAction,
+    ) -> Result<()> {
+        // The value replaced is recorded first, so the action can be undone.
+        let previous = match action.action {
+            ActionType::Rename => "COALESCE(original_description, description)",
+            ActionType::SetPayee => "payee",
+            ActionType::AddTag => concat!(
+                "EXISTS(SELECT 1 FROM transaction_tags AS t ",
+                "WHERE t.transaction_id=transactions.transaction_id AND t.tag=?)"
+            ),
+            ActionType::Transfer => "transfer_account",
+            ActionType::Review => "needs_review",
+        };
+        let record = format!(
+            "INSERT INTO transaction_rule_actions(action_id, action, value, previous, transaction_id) \
+             SELECT ?, ?, ?, {previous}, transaction_id FROM transactions WHERE transaction_id=?"
+        );
+        let mut query = sqlx::query(&record)
+            .bind(action.action_id)
+            .bind(action.action)
+            .bind(&action.value);
+        if action.action == ActionType::AddTag {
+            query = query.bind(&action.value);
+        }
+        query.bind(tx_id).execute(&mut *conn).await?;
+
+        let query = match action.action {
+            ActionType::Rename => sqlx::query(concat!(
+                "UPDATE transactions SET ",
+                "original_description=COALESCE(original_description, description), description=? ",
+                "WHERE transaction_id=? AND status<>'reconciled'"
+            ))
+            .bind(&action.value),
+            ActionType::SetPayee => sqlx::query(
+                "UPDATE transactions SET payee=? WHERE transaction_id=? AND status<>'reconciled'",
+            )
+            .bind(Some(action.value.as_str()).filter(|p| !p.is_empty())),
+            ActionType::AddTag => sqlx::query(concat!(
+                "INSERT OR IGNORE INTO transaction_tags(tag, transaction_id) ",
+                "SELECT ?, transaction_id FROM transactions ",
+                "WHERE transaction_id=? AND status<>'reconciled'"
+            ))
+            .bind(&action.value),
+            ActionType::Transfer => sqlx::query(concat!(
+                "UPDATE transactions SET transfer_account=? ",
+                "WHERE transaction_id=? AND status<>'reconciled'"
+            ))
+            .bind(action.transfer_account()),
+            ActionType::Review => sqlx::query(
+                "UPDATE transactions SET needs_review=1 WHERE transaction_id=? AND status<>'reconciled'",
+            ),
+        };
+        query.bind(tx_id).execute(conn).await?;
+        Ok(())
+    }
+
+    /// Restores the value an action replaced, and forgets it was applied.
+    async fn undo_action(
+        conn: &mut SqliteConnection,
+        tx_id: i32,
+        action_id: i32,
+        action: ActionType,
+    ) -> Result<()> {
+        let query = match action {
+            ActionType::Rename => concat!(
+                "UPDATE transactions SET description=r.previous, ",
+                "original_description=NULLIF(original_description, r.previous) ",
+                "FROM transaction_rule_actions AS r ",
+                "WHERE r.transaction_id=transactions.transaction_id ",
+                "AND r.action_id=? AND r.transaction_id=?"
+            ),
+            ActionType::SetPayee => concat!(
+                "UPDATE transactions SET payee=r.previous FROM transaction_rule_actions AS r ",
+                "WHERE r.transaction_id=transactions.transaction_id ",
+                "AND r.action_id=? AND r.transaction_id=?"
+            ),
+            // The tag is kept if the transaction already had it.
+            ActionType::AddTag => concat!(
+                "DELETE FROM transaction_tags WHERE (transaction_id, tag) IN (",
+                "SELECT transaction_id, value FROM transaction_rule_actions ",
+                "WHERE action_id=? AND transaction_id=? AND NOT previous)"
+            ),
+            ActionType::Transfer => concat!(
+                "UPDATE transactions SET transfer_account=r.previous ",
+                "FROM transaction_rule_actions AS r ",
+                "WHERE r.transaction_id=transactions.transaction_id ",
+                "AND r.action_id=? AND r.transaction_id=?"
+            ),
+            ActionType::Review => concat!(
+                "UPDATE transactions SET needs_review=r.previous ",
+                "FROM transaction_rule_actions AS r ",
+                "WHERE r.transaction_id=transactions.transaction_id ",
+                "AND r.action_id=? AND r.transaction_id=?"
+            ),
+        };
+        sqlx::query(query)
+            .bind(action_id)
+            .bind(tx_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM transaction_rule_actions WHERE action_id=? AND transaction_id=?")
+            .bind(action_id)
+            .bind(tx_id)
+            .execute(conn)
+            .await?;
+        Ok(())
     }
 
-    pub fn get_amount(&self) -> i32 {
+    pub fn get_amount(&self) -> Money {
         self.amount
     }
 
-    pub fn get_accumulated(&self) -> i32 {
+    pub fn get_accumulated(&self) -> Money {
         self.accumulated
     }
 
-    pub async fn set_description(&mut self, pool: &SqlitePool, desc: &str) -> Result<()> {
+    pub async fn set_payee(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        payee: Option<&str>,
+    ) -> Result<()> {
+        self.check_unlocked()?;
+        sqlx::query("UPDATE transactions SET payee=? WHERE transaction_id=?")
+            .bind(payee)
+            .bind(self.transaction_id)
+            .execute(conn)
+            .await?;
+        self.payee = payee.map(str::to_string);
+        Ok(())
+    }
+
+    pub async fn set_value_date(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        value_date: Option<NaiveDate>,
+    ) -> Result<()> {
+        self.check_unlocked()?;
+        sqlx::query("UPDATE transactions SET value_date=? WHERE transaction_id=?")
+            .bind(value_date)
+            .bind(self.transaction_id)
+            .execute(conn)
+            .await?;
+        self.value_date = value_date;
+        Ok(())
+    }
+
+    pub async fn set_timestamp(
+        &mut self,
+        pool: &SqlitePool,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        self.check_unlocked()?;
+        sqlx::query("UPDATE transactions SET tx_timestamp=? WHERE transaction_id=?")
+            .bind(timestamp)
+            .bind(self.transaction_id)
+            .execute(pool)
+            .await?;
+        self.tx_timestamp = timestamp;
+        Ok(())
+    }
+
+    pub async fn set_description(
+        &mut self,
+        conn: impl Executor<'_, Database = Sqlite>,
+        desc: &str,
+    ) -> Result<()> {
+        self.check_unlocked()?;
         sqlx::query("UPDATE transactions SET description=? WHERE transaction_id=?")
             .bind(desc)
             .bind(self.transaction_id)
-            .execute(pool)
+            .execute(conn)
             .await?;
         self.description = desc.to_string();
         Ok(())
     }
 
-    pub async fn set_amount(&mut self, pool: &SqlitePool, amount: i32) -> Result<()> {
+    /// Recomputes the running balance of every transaction of `account` dated on or after
+    /// `from`. The sum always starts at the first transaction of the account, so stale
+    /// values before `from` do not leak into the result.
+    async fn update_balances(
+        conn: &mut SqliteConnection,
+        account: i32,
+        from: NaiveDate,
+    ) -> Result<()> {
+        sqlx::query(concat!(
+            "UPDATE transactions SET accumulated=calc.acc FROM (",
+            "SELECT transaction_id, SUM(amount) OVER (",
+            "ORDER BY tx_date, tx_order, transaction_id ",
+            "ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW",
+            ") AS acc FROM transactions WHERE account=?",
+            ") AS calc ",
+            "WHERE transactions.transaction_id=calc.transaction_id ",
+            "AND transactions.tx_date >= ?"
+        ))
+        .bind(account)
+        .bind(from)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(self, conn: impl Acquire<'_, Database = Sqlite>) -> Result<()> {
+        self.check_unlocked()?;
+        let mut db_tx = conn.begin().await?;
+
+        sqlx::query("DELETE FROM transactions WHERE transaction_id=?")
+            .bind(self.transaction_id)
+            .execute(&mut *db_tx)
+            .await?;
+
+        sqlx::query(concat!(
+            "UPDATE transactions SET tx_order=tx_order-1 ",
+            "WHERE account=? AND tx_date=? AND tx_order>?"
+        ))
+        .bind(self.account)
+        .bind(self.tx_date)
+        .bind(self.tx_order)
+        .execute(&mut *db_tx)
+        .await?;
+
+        Self::update_balances(&mut db_tx, self.account, self.tx_date).await?;
+
+        db_tx.commit().await
+    }
+
+    /// Moves the transaction to another date. It is placed after the transactions already
+    /// booked on that day, and balances are recomputed from the earliest affected day.
+    pub async fn set_date(
+        &mut self,
+        conn: impl Acquire<'_, Database = Sqlite>,
+        date: NaiveDate,
+    ) -> Result<()> {
+        self.check_unlocked()?;
+        let mut db_tx = conn.begin().await?;
+
+        sqlx::query(concat!(
+            "UPDATE transactions SET tx_order=tx_order-1 ",
+            "WHERE account=? AND tx_date=? AND tx_order>?"
+        ))
+        .bind(self.account)
+        .bind(self.tx_date)
+        .bind(self.tx_order)
+        .execute(&mut *db_tx)
+        .await?;
+
+        let tx_order: i32 = sqlx::query_scalar(concat!(
+            "SELECT COALESCE(MAX(tx_order), 0)+1 FROM transactions ",
+            "WHERE account=? AND tx_date=? AND transaction_id<>?"
+        ))
+        .bind(self.account)
+        .bind(date)
+        .bind(self.transaction_id)
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        sqlx::query("UPDATE transactions SET tx_date=?, tx_order=? WHERE transaction_id=?")
+            .bind(date)
+            .bind(tx_order)
+            .bind(self.transaction_id)
+            .execute(&mut *db_tx)
+            .await?;
+
+        Self::update_balances(&mut db_tx, self.account, date.min(self.tx_date)).await?;
+
+        let accumulated =
+            sqlx::query_scalar("SELECT accumulated FROM transactions WHERE transaction_id=?")
+                .bind(self.transaction_id)
+                .fetch_one(&mut *db_tx)
+                .await?;
+
+        db_tx.commit().await?;
+        self.tx_date = date;
+        self.tx_order = tx_order;
+        self.accumulated = accumulated;
+        Ok(())
+    }
+
+    /// Swaps the transaction with its neighbour on the same day. Returns `false` when it is
+    /// already the first (or last) one of the day.
+    pub async fn move_within_day(
+        &mut self,
+        conn: impl Acquire<'_, Database = Sqlite>,
+        direction: MoveDirection,
+    ) -> Result<bool> {
+        self.check_unlocked()?;
+        let mut db_tx = conn.begin().await?;
+
+        let neighbour: Option<(i32, i32)> = sqlx::query_as(match direction {
+            MoveDirection::Up => concat!(
+                "SELECT transaction_id, tx_order FROM transactions ",
+                "WHERE account=? AND tx_date=? AND tx_order<? ",
+                "ORDER BY tx_order DESC LIMIT 1"
+            ),
+            MoveDirection::Down => concat!(
+                "SELECT transaction_id, tx_order FROM transactions ",
+                "WHERE account=? AND tx_date=? AND tx_order>? ",
+                "ORDER BY tx_order ASC LIMIT 1"
+            ),
+        })
+        .bind(self.account)
+        .bind(self.tx_date)
+        .bind(self.tx_order)
+        .fetch_optional(&mut *db_tx)
+        .await?;
+
+        let (neighbour_id, neighbour_order) = match neighbour {
+            Some(n) => n,
+            None => return Ok(false),
+        };
+
+        sqlx::query("UPDATE transactions SET tx_order=? WHERE transaction_id=?")
+            .bind(self.tx_order)
+            .bind(neighbour_id)
+            .execute(&mut *db_tx)
+            .await?;
+        sqlx::query("UPDATE transactions SET tx_order=? WHERE transaction_id=?")
+            .bind(neighbour_order)
+            .bind(self.transaction_id)
+            .execute(&mut *db_tx)
+            .await?;
+
+        Self::update_balances(&mut db_tx, self.account, self.tx_date).await?;
+
+        let accumulated =
+            sqlx::query_scalar("SELECT accumulated FROM transactions WHERE transaction_id=?")
+                .bind(self.transaction_id)
+                .fetch_one(&mut *db_tx)
+                .await?;
+
+        db_tx.commit().await?;
+        self.tx_order = neighbour_order;
+        self.accumulated = accumulated;
+        Ok(true)
+    }
+
+    pub async fn set_amount(&mut self, pool: &SqlitePool, amount: Money) -> Result<()> {
+        self.check_unlocked()?;
+        let mut db_tx = pool.begin().await?;
+
         sqlx::query("UPDATE transactions SET amount=? WHERE transaction_id=?")
             .bind(amount)
             .bind(self.transaction_id)
-            .execute(pool)
+            .execute(&mut *db_tx)
             .await?;
-        self.accumulated -= self.amount;
+
+        Self::update_balances(&mut db_tx, self.account, self.tx_date).await?;
+
+        let accumulated =
+            sqlx::query_scalar("SELECT accumulated FROM transactions WHERE transaction_id=?")
+                .bind(self.transaction_id)
+                .fetch_one(&mut *db_tx)
+                .await?;
+
+        db_tx.commit().await?;
         self.amount = amount;
-        self.accumulated += self.amount;
+        self.accumulated = accumulated;
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Transaction;
-    use crate::models::account::Account;
+    use super::{MoveDirection, NewTransaction, Transaction, TransactionStatus};
+    use crate::models::{
+        account::{Account, AccountType},
+        money::Money,
+    };
+    use chrono::NaiveDate;
     use sqlx::SqlitePool;
 
-    async fn get_db() -> SqlitePool {
-        crate::create_db("sqlite://tx_test.db").await.unwrap()
+    async fn get_db(name: &str) -> SqlitePool {
+        crate::create_db(&format!("sqlite://{name}")).await.unwrap()
     }
 
-    async fn remove_db(pool: SqlitePool) {
+    async fn remove_db(pool: SqlitePool, name: &str) {
         pool.close().await;
-        std::fs::remove_file("tx_test.db").unwrap();
+        std::fs::remove_file(name).unwrap();
     }
 
     #[tokio::test]
     async fn create_test() {
-        let pool = get_db().await;
-        let acc = Account::new(&pool, "tx_test").await.unwrap();
+        let pool = get_db("tx_test.db").await;
+        let acc = Account::new(&pool, "tx_test", AccountType::Checking, None)
+            .await
+            .unwrap();
         let tx = Transaction::new(
             &pool,
             acc.get_id(),
             "Test transaction",
-            &chrono::Utc::now(),
+            chrono::Utc::now().date_naive(),
             None,
-            100,
+            Money::from_cents(100),
+            TransactionStatus::Cleared,
         )
         .await
         .unwrap();
 
         println!("{tx:?}");
 
-        remove_db(pool).await;
+        remove_db(pool, "tx_test.db").await;
+    }
+
+    #[tokio::test]
+    async fn delete_test() {
+        let pool = get_db("tx_delete_test.db").await;
+        let acc = Account::new(&pool, "tx_delete_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let day = chrono::Utc::now().date_naive();
+        let mut ids = Vec::new();
+        for (offset, amount) in [(0, 1000), (1, -200), (1, -300), (2, 50)] {
+            let tx = Transaction::new(
+                &pool,
+                acc.get_id(),
+                "Test transaction",
+                day + chrono::Duration::days(offset),
+                None,
+                Money::from_cents(amount),
+                TransactionStatus::Cleared,
+            )
+            .await
+            .unwrap();
+            ids.push(tx.get_id());
+        }
+
+        Transaction::get_by_id(&pool, ids[1])
+            .await
+            .unwrap()
+            .delete(&pool)
+            .await
+            .unwrap();
+
+        let same_day = Transaction::get_by_id(&pool, ids[2]).await.unwrap();
+        assert_eq!(same_day.tx_order, 1);
+        assert_eq!(same_day.get_accumulated(), Money::from_cents(700));
+        let last = Transaction::get_by_id(&pool, ids[3]).await.unwrap();
+        assert_eq!(last.get_accumulated(), Money::from_cents(750));
+
+        remove_db(pool, "tx_delete_test.db").await;
+    }
+
+    #[tokio::test]
+    async fn set_amount_test() {
+        let pool = get_db("tx_amount_test.db").await;
+        let acc = Account::new(&pool, "tx_amount_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let day = chrono::Utc::now().date_naive();
+        let mut txs = Vec::new();
+        for (offset, amount) in [(0, 1000), (1, -200), (2, 50)] {
+            txs.push(
+                Transaction::new(
+                    &pool,
+                    acc.get_id(),
+                    "Test transaction",
+                    day + chrono::Duration::days(offset),
+                    None,
+                    Money::from_cents(amount),
+                    TransactionStatus::Cleared,
+                )
+                .await
+                .unwrap(),
+            );
+        }
+
+        txs[1]
+            .set_amount(&pool, Money::from_cents(-500))
+            .await
+            .unwrap();
+        assert_eq!(txs[1].get_accumulated(), Money::from_cents(500));
+        let stored = Transaction::get_by_id(&pool, txs[1].get_id())
+            .await
+            .unwrap();
+        assert_eq!(stored.get_accumulated(), Money::from_cents(500));
+        let last = Transaction::get_by_id(&pool, txs[2].get_id())
+            .await
+            .unwrap();
+        assert_eq!(last.get_accumulated(), Money::from_cents(550));
+
+        remove_db(pool, "tx_amount_test.db").await;
+    }
+
+    #[tokio::test]
+    async fn set_date_test() {
+        let pool = get_db("tx_date_test.db").await;
+        let acc = Account::new(&pool, "tx_date_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let day = chrono::Utc::now().date_naive();
+        let mut txs = Vec::new();
+        for (offset, amount) in [(0, 1000), (1, -200), (2, 50), (3, -10)] {
+            txs.push(
+                Transaction::new(
+                    &pool,
+                    acc.get_id(),
+                    "Test transaction",
+                    day + chrono::Duration::days(offset),
+                    None,
+                    Money::from_cents(amount),
+                    TransactionStatus::Cleared,
+                )
+                .await
+                .unwrap(),
+            );
+        }
+
+        // Move the second transaction after the third one, on the same day as the fourth.
+        txs[1]
+            .set_date(&pool, day + chrono::Duration::days(3))
+            .await
+            .unwrap();
+        assert_eq!(txs[1].get_accumulated(), Money::from_cents(840));
+
+        let moved_over = Transaction::get_by_id(&pool, txs[2].get_id())
+            .await
+            .unwrap();
+        assert_eq!(moved_over.get_accumulated(), Money::from_cents(1050));
+        let same_day = Transaction::get_by_id(&pool, txs[3].get_id())
+            .await
+            .unwrap();
+        assert_eq!(same_day.get_accumulated(), Money::from_cents(1040));
+        assert!(same_day.tx_order < txs[1].tx_order);
+
+        remove_db(pool, "tx_date_test.db").await;
+    }
+
+    #[tokio::test]
+    async fn group_by_date_test() {
+        let pool = get_db("tx_group_test.db").await;
+        let acc = Account::new(&pool, "tx_group_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let day = NaiveDate::from_ymd_opt(2023, 12, 1).unwrap();
+        for (offset, amount) in [(0, 1000), (0, -200), (1, -50)] {
+            Transaction::new(
+                &pool,
+                acc.get_id(),
+                "Test transaction",
+                day + chrono::Duration::days(offset),
+                None,
+                Money::from_cents(amount),
+                TransactionStatus::Cleared,
+            )
+            .await
+            .unwrap();
+        }
+
+        let agg = Transaction::group_by_date(&pool, acc.get_id(), Some(day), Some(day), true)
+            .await
+            .unwrap();
+        assert_eq!(agg.len(), 1);
+        assert_eq!(agg[0].tx_date, day);
+        assert_eq!(agg[0].accumulated, Money::from_cents(800));
+
+        let mut last =
+            Transaction::list_by_date(&pool, Some(acc.get_id()), None, None, None, false)
+                .await
+                .unwrap()
+                .remove(0);
+        last.set_value_date(&pool, Some(day + chrono::Duration::days(2)))
+            .await
+            .unwrap();
+        let stored = Transaction::get_by_id(&pool, last.get_id()).await.unwrap();
+        assert_eq!(stored.get_date(), day + chrono::Duration::days(1));
+        assert_eq!(
+            stored.get_value_date(),
+            Some(day + chrono::Duration::days(2))
+        );
+        assert!(stored.get_timestamp().is_none());
+
+        remove_db(pool, "tx_group_test.db").await;
+    }
+
+    #[tokio::test]
+    async fn order_test() {
+        let pool = get_db("tx_order_test.db").await;
+        let acc = Account::new(&pool, "tx_order_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let other = Account::new(&pool, "tx_order_other", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let day = chrono::Utc::now().date_naive();
+
+        Transaction::new(
+            &pool,
+            other.get_id(),
+            "Other",
+            day,
+            None,
+            Money::from_cents(1),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+        let mut first = Transaction::new(
+            &pool,
+            acc.get_id(),
+            "First",
+            day,
+            None,
+            Money::from_cents(100),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+        let second = Transaction::new(
+            &pool,
+            acc.get_id(),
+            "Second",
+            day,
+            None,
+            Money::from_cents(-30),
+            TransactionStatus::Cleared,
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.tx_order, 1);
+        assert_eq!(first.get_accumulated(), Money::from_cents(100));
+        assert_eq!(second.tx_order, 2);
+        assert_eq!(second.get_accumulated(), Money::from_cents(70));
+
+        assert!(!first
+            .move_within_day(&pool, MoveDirection::Up)
+            .await
+            .unwrap());
+        assert!(first
+            .move_within_day(&pool, MoveDirection::Down)
+            .await
+            .unwrap());
+        assert_eq!(first.tx_order, 2);
+        assert_eq!(first.get_accumulated(), Money::from_cents(70));
+        let second = Transaction::get_by_id(&pool, second.get_id())
+            .await
+            .unwrap();
+        assert_eq!(second.tx_order, 1);
+        assert_eq!(second.get_accumulated(), Money::from_cents(-30));
+
+        remove_db(pool, "tx_order_test.db").await;
+    }
+
+    #[tokio::test]
+    async fn reconciled_test() {
+        let pool = get_db("tx_reconciled_test.db").await;
+        let acc = Account::new(&pool, "tx_reconciled_test", AccountType::Checking, None)
+            .await
+            .unwrap();
+        let mut tx = Transaction::new(
+            &pool,
+            acc.get_id(),
+            "Card payment",
+            chrono::Utc::now().date_naive(),
+            None,
+            Money::from_cents(-100),
+            TransactionStatus::Pending,
+        )
+        .await
+        .unwrap();
+        assert_eq!(tx.get_status(), TransactionStatus::Pending);
+
+        tx.set_status(&pool, TransactionStatus::Reconciled)
+            .await
+            .unwrap();
+        assert!(tx.set_amount(&pool, Money::from_cents(-200)).await.is_err());
+        assert_eq!(
+            Transaction::get_by_id(&pool, tx.get_id())
+                .await
+                .unwrap()
+                .get_amount(),
+            Money::from_cents(-100)
+        );
+
+        tx.set_status(&pool, TransactionStatus::Cleared)
+            .await
+            .unwrap();
+        tx.set_amount(&pool, Money::from_cents(-200)).await.unwrap();
+
+        // Optional fields are stored along with the row, even for a reconciled transaction.
+        let date = NaiveDate::from_ymd_opt(2023, 12, 1).unwrap();
+        let tx = Transaction::insert(
+            &pool,
+            &NewTransaction {
+                account: acc.get_id(),
+                description: "Transfer",
+                date,
+                category: None,
+                amount: Money::from_cents(-300),
+                status: TransactionStatus::Reconciled,
+                payee: Some("Landlord"),
+                value_date: Some(date.succ_opt().unwrap()),
+                timestamp: None,
+                scheduled: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(tx.get_status(), TransactionStatus::Reconciled);
+        assert_eq!(tx.get_payee(), Some("Landlord"));
+        assert_eq!(tx.get_value_date(), date.succ_opt());
+
+        remove_db(pool, "tx_reconciled_test.db").await;
     }
 }
@@ -1,17 +1,85 @@
-use std::sync::Arc;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
 
-use axum::extract::{Json, State};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Json, State},
+    http::request::Parts,
+};
 use hyper::StatusCode;
 use serde::Deserialize;
-use sqlx::SqlitePool;
+use sqlx::{Sqlite, SqliteConnection, SqlitePool, Transaction};
 
 use accounters::models::users::User;
 
 pub mod accounts;
+pub mod audit;
+pub mod budgets;
 pub mod categories;
+pub mod goals;
+pub mod investments;
+pub mod loans;
 pub mod rules;
+pub mod scheduled;
+pub mod subscriptions;
 pub mod transactions;
 
+/// Header with the id returned by the login, naming the user the changes of a request are
+/// recorded for in the audit log.
+const USER_HEADER: &str = "x-user-id";
+
+/// Database transaction of a request that changes data, with the user of the request set
+/// for the audit log. Nothing is stored unless the handler calls [`AuditTx::commit`].
+pub struct AuditTx(Transaction<'static, Sqlite>);
+
+impl AuditTx {
+    pub async fn commit(self) -> sqlx::Result<()> {
+        accounters::models::audit::commit(self.0).await
+    }
+}
+
+impl Deref for AuditTx {
+    type Target = SqliteConnection;
+
+    fn deref(&self) -> &SqliteConnection {
+        &self.0
+    }
+}
+
+impl DerefMut for AuditTx {
+    fn deref_mut(&mut self) -> &mut SqliteConnection {
+        &mut self.0
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuditTx
+where
+    Arc<SqlitePool>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = match parts.headers.get(USER_HEADER).map(|v| v.to_str()) {
+            None => None,
+            Some(Ok(v)) => match v.trim().parse::<i32>() {
+                Ok(id) => Some(id),
+                Err(e) => return Err((StatusCode::BAD_REQUEST, format!("{e}"))),
+            },
+            Some(Err(e)) => return Err((StatusCode::BAD_REQUEST, format!("{e}"))),
+        };
+
+        let db = Arc::<SqlitePool>::from_ref(state);
+        accounters::models::audit::begin(db.as_ref(), user)
+            .await
+            .map(AuditTx)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")))
+    }
+}
+
 #[derive(Deserialize)]
 pub struct CreateUserRequest {
     user: String,
@@ -1,14 +1,21 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Json, Path, State},
+    extract::{Json, Path, Query, State},
     response::IntoResponse,
 };
+use chrono::Utc;
 use hyper::{header::CONTENT_TYPE, StatusCode};
 use serde::Deserialize;
 use sqlx::SqlitePool;
 
-use accounters::models::account::Account;
+use accounters::models::{
+    account::{Account, AccountType, RecategorizeMode},
+    forecast::{Forecast, DEFAULT_FORECAST_DAYS, MAX_FORECAST_DAYS},
+    rule_engine::RuleCache,
+};
+
+use super::AuditTx;
 
 pub async fn account_get(
     State(db): State<Arc<SqlitePool>>,
@@ -31,13 +38,27 @@ pub async fn account_get(
 #[derive(Deserialize)]
 pub struct AccountRequestCreate {
     pub name: String,
+    #[serde(default)]
+    pub account_type: AccountType,
+    pub liability: Option<bool>,
 }
 
 pub async fn account_create(
-    State(db): State<Arc<SqlitePool>>,
+    mut db_tx: AuditTx,
     Json(account): Json<AccountRequestCreate>,
 ) -> impl IntoResponse {
-    match Account::new(db.as_ref(), &account.name).await {
+    let res = match Account::new(
+        &mut *db_tx,
+        &account.name,
+        account.account_type,
+        account.liability,
+    )
+    .await
+    {
+        Ok(a) => db_tx.commit().await.map(|_| a),
+        Err(e) => Err(e),
+    };
+    match res {
         Ok(a) => (
             StatusCode::OK,
             [(CONTENT_TYPE, "application/json")],
@@ -51,8 +72,16 @@ pub async fn account_create(
     }
 }
 
-pub async fn account_list(State(db): State<Arc<SqlitePool>>) -> impl IntoResponse {
-    match Account::list(db.as_ref()).await {
+#[derive(Deserialize)]
+pub struct AccountListParams {
+    pub archived: Option<bool>,
+}
+
+pub async fn account_list(
+    State(db): State<Arc<SqlitePool>>,
+    Query(params): Query<AccountListParams>,
+) -> impl IntoResponse {
+    match Account::list(db.as_ref(), params.archived.unwrap_or(false)).await {
         Ok(a) => (
             StatusCode::OK,
             [(CONTENT_TYPE, "application/json")],
@@ -66,17 +95,266 @@ pub async fn account_list(State(db): State<Arc<SqlitePool>>) -> impl IntoRespons
     }
 }
 
+async fn set_archived(mut db_tx: AuditTx, id: i32, archived: bool) -> impl IntoResponse {
+    let mut account = match Account::get_by_id(&mut *db_tx, id).await {
+        Ok(a) => a,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e}"),
+            )
+        }
+    };
+
+    let res = match account.set_archived(&mut *db_tx, archived).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    match res {
+        Ok(_) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&account).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
+    }
+}
+
+pub async fn account_archive(db_tx: AuditTx, Path(id): Path<i32>) -> impl IntoResponse {
+    set_archived(db_tx, id, true).await
+}
+
+pub async fn account_unarchive(db_tx: AuditTx, Path(id): Path<i32>) -> impl IntoResponse {
+    set_archived(db_tx, id, false).await
+}
+
+#[derive(Deserialize)]
+pub struct AccountUpdateRequest {
+    auto_classify: Option<bool>,
+}
+
+pub async fn account_update(
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+    Json(req): Json<AccountUpdateRequest>,
+) -> impl IntoResponse {
+    let mut account = match Account::get_by_id(&mut *db_tx, id).await {
+        Ok(a) => a,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e}"),
+            )
+        }
+    };
+
+    if let Some(auto_classify) = req.auto_classify {
+        if let Err(e) = account.set_auto_classify(&mut *db_tx, auto_classify).await {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e}"),
+            );
+        }
+    }
+
+    if let Err(e) = db_tx.commit().await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "application/json")],
+        serde_json::to_string(&account).unwrap(),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct AccountDeleteParams {
+    /// Must repeat the account name, so an account is never removed by mistake.
+    pub confirm: Option<String>,
+}
+
+pub async fn account_delete(
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+    Query(params): Query<AccountDeleteParams>,
+) -> impl IntoResponse {
+    let account = match Account::get_by_id(&mut *db_tx, id).await {
+        Ok(a) => a,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    if params.confirm.as_deref() != Some(account.get_account_name()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            String::from("Deleting an account must be confirmed with its name"),
+        );
+    }
+
+    let res = match account.delete(&mut *db_tx).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    match res {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
+    }
+}
+
+pub async fn account_balances(
+    State(db): State<Arc<SqlitePool>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let account = match Account::get_by_id(db.as_ref(), id).await {
+        Ok(a) => a,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e}"),
+            )
+        }
+    };
+
+    match account.get_balances(db.as_ref()).await {
+        Ok(b) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&b).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ForecastParams {
+    days: Option<u32>,
+}
+
+pub async fn account_forecast(
+    State(db): State<Arc<SqlitePool>>,
+    Path(id): Path<i32>,
+    Query(params): Query<ForecastParams>,
+) -> impl IntoResponse {
+    let account = match Account::get_by_id(db.as_ref(), id).await {
+        Ok(a) => a,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e}"),
+            )
+        }
+    };
+
+    let days = params
+        .days
+        .unwrap_or(DEFAULT_FORECAST_DAYS)
+        .min(MAX_FORECAST_DAYS);
+    match Forecast::for_account(db.as_ref(), &account, Utc::now().date_naive(), days).await {
+        Ok(f) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&f).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
+    }
+}
+
+pub async fn net_worth(State(db): State<Arc<SqlitePool>>) -> impl IntoResponse {
+    match Account::net_worth(db.as_ref()).await {
+        Ok(nw) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&nw).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RecategorizeParams {
+    #[serde(default)]
+    mode: RecategorizeMode,
+}
+
 pub async fn recategorize(
     State(db): State<Arc<SqlitePool>>,
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
     Path(account): Path<i32>,
+    Query(params): Query<RecategorizeParams>,
 ) -> impl IntoResponse {
-    let account = Account::get_by_id(db.as_ref(), account).await.unwrap();
+    let account = Account::get_by_id(&mut *db_tx, account).await.unwrap();
 
-    match account.recategorize_transactions(db.as_ref()).await {
-        Ok(_) => (
+    let updated = match rules.get(db.as_ref()).await {
+        Ok(engine) => {
+            account
+                .recategorize_transactions(&mut *db_tx, &engine, params.mode)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    let updated = match updated {
+        Ok(n) => db_tx.commit().await.map(|_| n),
+        Err(e) => Err(e),
+    };
+    match updated {
+        Ok(n) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{n}"),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
+    }
+}
+
+pub async fn recategorize_all(
+    State(db): State<Arc<SqlitePool>>,
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
+    Query(params): Query<RecategorizeParams>,
+) -> impl IntoResponse {
+    let updated = match rules.get(db.as_ref()).await {
+        Ok(engine) => Account::recategorize_all(&mut *db_tx, &engine, params.mode).await,
+        Err(e) => Err(e),
+    };
+    let updated = match updated {
+        Ok(n) => db_tx.commit().await.map(|_| n),
+        Err(e) => Err(e),
+    };
+    match updated {
+        Ok(n) => (
             StatusCode::OK,
             [(CONTENT_TYPE, "text/plain")],
-            String::new(),
+            format!("{n}"),
         ),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
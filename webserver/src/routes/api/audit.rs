@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use accounters::models::audit::AuditEntry;
+
+#[derive(Deserialize)]
+pub struct AuditListParams {
+    table: Option<String>,
+    row: Option<i32>,
+    entries: Option<i32>,
+    page: Option<i32>,
+}
+
+/// Lists the history of a row when `table` and `row` are given, or the latest changes
+/// otherwise.
+pub async fn list(
+    State(db): State<Arc<SqlitePool>>,
+    Query(params): Query<AuditListParams>,
+) -> impl IntoResponse {
+    let entries = match (params.table, params.row) {
+        (Some(table), Some(row)) => AuditEntry::list_for_row(db.as_ref(), &table, row).await,
+        _ => {
+            let limit = params.entries.unwrap_or(50);
+            let offset = params.page.unwrap_or(0) * limit;
+            AuditEntry::list(db.as_ref(), limit, offset).await
+        }
+    };
+
+    match entries {
+        Ok(e) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&e).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Json, Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::{NaiveDate, Utc};
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use accounters::models::{
+    budgets::{parse_month, Budget},
+    money::Money,
+};
+
+#[derive(Deserialize)]
+pub struct BudgetCreateRequest {
+    category: i32,
+    amount: Money,
+    #[serde(default)]
+    rollover: bool,
+    start_month: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+pub struct BudgetUpdateRequest {
+    amount: Option<Money>,
+    rollover: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct BudgetListParams {
+    /// Month to report, as `YYYY-MM`. Defaults to the current one.
+    month: Option<String>,
+}
+
+pub async fn create(
+    State(db): State<Arc<SqlitePool>>,
+    Json(req): Json<BudgetCreateRequest>,
+) -> impl IntoResponse {
+    let start = req.start_month.unwrap_or(Utc::now().date_naive());
+    match Budget::new(db.as_ref(), req.category, req.amount, req.rollover, start).await {
+        Ok(b) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&b).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn list(
+    State(db): State<Arc<SqlitePool>>,
+    Query(params): Query<BudgetListParams>,
+) -> impl IntoResponse {
+    let month = match params.month {
+        Some(m) => match parse_month(&m) {
+            Some(m) => m,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    [(CONTENT_TYPE, "text/plain")],
+                    String::from("month must be formatted as YYYY-MM"),
+                )
+            }
+        },
+        None => Utc::now().date_naive(),
+    };
+
+    match Budget::list_status(db.as_ref(), month).await {
+        Ok(s) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&s).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn update(
+    State(db): State<Arc<SqlitePool>>,
+    Path(id): Path<i32>,
+    Json(req): Json<BudgetUpdateRequest>,
+) -> impl IntoResponse {
+    let mut budget = match Budget::get_by_id(db.as_ref(), id).await {
+        Ok(b) => b,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e:?}")),
+    };
+
+    if let Some(amount) = req.amount {
+        if let Err(e) = budget.set_amount(db.as_ref(), amount).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}"));
+        }
+    }
+    if let Some(rollover) = req.rollover {
+        if let Err(e) = budget.set_rollover(db.as_ref(), rollover).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}"));
+        }
+    }
+
+    (StatusCode::OK, serde_json::to_string(&budget).unwrap())
+}
+
+pub async fn delete(State(db): State<Arc<SqlitePool>>, Path(id): Path<i32>) -> impl IntoResponse {
+    let budget = match Budget::get_by_id(db.as_ref(), id).await {
+        Ok(b) => b,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e:?}")),
+    };
+
+    match budget.delete(db.as_ref()).await {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+    }
+}
@@ -7,6 +7,8 @@ use sqlx::SqlitePool;
 
 use accounters::models::categories::Category;
 
+use super::AuditTx;
+
 #[derive(Deserialize)]
 pub struct CategoryCreateRequest {
     name: String,
@@ -14,10 +16,15 @@ pub struct CategoryCreateRequest {
 }
 
 pub async fn create(
-    State(db): State<Arc<SqlitePool>>,
+    mut db_tx: AuditTx,
     Json(new_category): Json<CategoryCreateRequest>,
 ) -> impl IntoResponse {
-    match Category::new(db.as_ref(), &new_category.name, &new_category.description).await {
+    let res = match Category::new(&mut *db_tx, &new_category.name, &new_category.description).await
+    {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    match res {
         Ok(_) => (StatusCode::OK, String::new()),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
     }
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Json, Path, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use accounters::models::{goals::Goal, money::Money};
+
+fn default_share() -> i32 {
+    100
+}
+
+#[derive(Deserialize)]
+pub struct GoalCreateRequest {
+    name: String,
+    account: i32,
+    #[serde(default = "default_share")]
+    share: i32,
+    target_amount: Money,
+    target_date: NaiveDate,
+}
+
+pub async fn create(
+    State(db): State<Arc<SqlitePool>>,
+    Json(req): Json<GoalCreateRequest>,
+) -> impl IntoResponse {
+    match Goal::new(
+        db.as_ref(),
+        &req.name,
+        req.account,
+        req.share,
+        req.target_amount,
+        req.target_date,
+    )
+    .await
+    {
+        Ok(g) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&g).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn list(State(db): State<Arc<SqlitePool>>) -> impl IntoResponse {
+    match Goal::list_progress(db.as_ref()).await {
+        Ok(g) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&g).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn delete(State(db): State<Arc<SqlitePool>>, Path(id): Path<i32>) -> impl IntoResponse {
+    let goal = match Goal::get_by_id(db.as_ref(), id).await {
+        Ok(g) => g,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e:?}")),
+    };
+
+    match goal.delete(db.as_ref()).await {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Json, Path, State},
+    response::IntoResponse,
+};
+use chrono::{NaiveDate, Utc};
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use accounters::models::{
+    investments::{Portfolio, Security, Trade, TradeKind},
+    money::Money,
+};
+
+#[derive(Deserialize)]
+pub struct SecurityCreateRequest {
+    symbol: String,
+    name: String,
+}
+
+pub async fn security_create(
+    State(db): State<Arc<SqlitePool>>,
+    Json(req): Json<SecurityCreateRequest>,
+) -> impl IntoResponse {
+    match Security::new(db.as_ref(), &req.symbol, &req.name).await {
+        Ok(s) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&s).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn security_list(State(db): State<Arc<SqlitePool>>) -> impl IntoResponse {
+    match Security::list(db.as_ref()).await {
+        Ok(s) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&s).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+/// Loads prices from a CSV body with `symbol,date,price` lines.
+pub async fn prices_import(State(db): State<Arc<SqlitePool>>, body: String) -> impl IntoResponse {
+    match Security::import_prices_csv(db.as_ref(), &body).await {
+        Ok(n) => (StatusCode::OK, format!("{n}")),
+        Err(e @ sqlx::Error::Protocol(_)) => (StatusCode::BAD_REQUEST, format!("{e}")),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TradeCreateRequest {
+    security: i32,
+    date: NaiveDate,
+    kind: TradeKind,
+    #[serde(default)]
+    quantity: f64,
+    #[serde(default)]
+    price: Money,
+    #[serde(default)]
+    fees: Money,
+    /// Cash received, only used for dividends.
+    #[serde(default)]
+    amount: Money,
+}
+
+pub async fn trade_create(
+    State(db): State<Arc<SqlitePool>>,
+    Path(account): Path<i32>,
+    Json(req): Json<TradeCreateRequest>,
+) -> impl IntoResponse {
+    let trade = match req.kind {
+        TradeKind::Dividend => {
+            Trade::dividend(db.as_ref(), account, req.security, req.date, req.amount).await
+        }
+        kind => {
+            Trade::new(
+                db.as_ref(),
+                account,
+                req.security,
+                req.date,
+                kind,
+                req.quantity,
+                req.price,
+                req.fees,
+            )
+            .await
+        }
+    };
+
+    match trade {
+        Ok(t) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&t).unwrap(),
+        ),
+        Err(e @ sqlx::Error::Protocol(_)) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn trade_list(
+    State(db): State<Arc<SqlitePool>>,
+    Path(account): Path<i32>,
+) -> impl IntoResponse {
+    match Trade::list_by_account(db.as_ref(), account).await {
+        Ok(t) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&t).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn trade_delete(
+    State(db): State<Arc<SqlitePool>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let trade = match Trade::get_by_id(db.as_ref(), id).await {
+        Ok(t) => t,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e:?}")),
+    };
+
+    match trade.delete(db.as_ref()).await {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e @ sqlx::Error::Protocol(_)) => (StatusCode::BAD_REQUEST, format!("{e}")),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+    }
+}
+
+pub async fn portfolio(
+    State(db): State<Arc<SqlitePool>>,
+    Path(account): Path<i32>,
+) -> impl IntoResponse {
+    match Portfolio::for_account(db.as_ref(), account, Utc::now().date_naive()).await {
+        Ok(p) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&p).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Json, Path, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use accounters::models::{
+    loans::{Installment, Loan},
+    money::Money,
+};
+
+#[derive(Deserialize)]
+pub struct LoanCreateRequest {
+    account: i32,
+    principal: Money,
+    annual_rate: f64,
+    term_months: i32,
+    start_date: NaiveDate,
+    payment_account: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct LoanResponse {
+    #[serde(flatten)]
+    loan: Loan,
+    monthly_payment: Money,
+    schedule: Vec<Installment>,
+}
+
+pub async fn create(
+    State(db): State<Arc<SqlitePool>>,
+    Json(req): Json<LoanCreateRequest>,
+) -> impl IntoResponse {
+    let created = Loan::new(
+        db.as_ref(),
+        req.account,
+        req.principal,
+        req.annual_rate,
+        req.term_months,
+        req.start_date,
+        req.payment_account,
+    )
+    .await;
+
+    let loan = match created {
+        Ok(l) => l,
+        Err(e @ sqlx::Error::Protocol(_)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e}"),
+            )
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e:?}"),
+            )
+        }
+    };
+
+    // Payments imported before the loan was entered are matched right away.
+    if let Err(e) = loan.match_payments(db.as_ref()).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "application/json")],
+        serde_json::to_string(&loan).unwrap(),
+    )
+}
+
+pub async fn list(State(db): State<Arc<SqlitePool>>) -> impl IntoResponse {
+    match Loan::list(db.as_ref()).await {
+        Ok(l) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&l).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn get(State(db): State<Arc<SqlitePool>>, Path(id): Path<i32>) -> impl IntoResponse {
+    let loan = match Loan::get_by_id(db.as_ref(), id).await {
+        Ok(l) => l,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e:?}"),
+            )
+        }
+    };
+
+    match loan.schedule(db.as_ref()).await {
+        Ok(schedule) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&LoanResponse {
+                monthly_payment: loan.monthly_payment(),
+                loan,
+                schedule,
+            })
+            .unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn delete(State(db): State<Arc<SqlitePool>>, Path(id): Path<i32>) -> impl IntoResponse {
+    let loan = match Loan::get_by_id(db.as_ref(), id).await {
+        Ok(l) => l,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e:?}")),
+    };
+
+    match loan.delete(db.as_ref()).await {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+    }
+}
@@ -1,31 +1,161 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Json, State},
+    extract::{Json, Path, State},
     response::IntoResponse,
 };
 use hyper::{header::CONTENT_TYPE, StatusCode};
 use serde::Deserialize;
-use sqlx::SqlitePool;
+use sqlx::{SqliteConnection, SqlitePool};
 
-use accounters::models::rules::Rule;
+use accounters::models::{
+    rule_engine::{RuleCache, RulePreview},
+    rules::{ActionType, ConditionField, ConditionOperator, Rule, RuleAction, RuleCondition},
+    transaction::MoveDirection,
+};
+
+use super::AuditTx;
+
+#[derive(Deserialize)]
+pub struct ConditionRequest {
+    field: ConditionField,
+    operator: ConditionOperator,
+    value: String,
+}
+
+#[derive(Deserialize)]
+pub struct ActionRequest {
+    action: ActionType,
+    #[serde(default)]
+    value: String,
+}
+
+fn default_match_all() -> bool {
+    true
+}
 
 #[derive(Deserialize)]
 pub struct RuleCreateRequest {
+    #[serde(default)]
     regex: String,
     category: i32,
+    #[serde(default = "default_match_all")]
+    match_all: bool,
+    #[serde(default)]
+    conditions: Vec<ConditionRequest>,
+    #[serde(default)]
+    actions: Vec<ActionRequest>,
+}
+
+async fn create_rule(conn: &mut SqliteConnection, req: RuleCreateRequest) -> sqlx::Result<Rule> {
+    for c in req.conditions.iter() {
+        RuleCondition::validate(c.field, c.operator, &c.value).map_err(sqlx::Error::Protocol)?;
+    }
+    for a in req.actions.iter() {
+        RuleAction::validate(a.action, &a.value).map_err(sqlx::Error::Protocol)?;
+    }
+
+    let mut rule = Rule::new(&mut *conn, req.regex, req.category).await?;
+    if !req.match_all {
+        rule.set_match_all(&mut *conn, false).await?;
+    }
+    for c in req.conditions.iter() {
+        RuleCondition::new(&mut *conn, rule.rule_id, c.field, c.operator, &c.value).await?;
+    }
+    for a in req.actions.iter() {
+        RuleAction::new(&mut *conn, rule.rule_id, a.action, &a.value).await?;
+    }
+    Rule::get_by_id(conn, rule.rule_id).await
 }
 
 pub async fn create(
-    State(db): State<Arc<SqlitePool>>,
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
     Json(rule): Json<RuleCreateRequest>,
 ) -> impl IntoResponse {
-    match Rule::new(db.as_ref(), rule.regex, rule.category).await {
+    let res = match create_rule(&mut db_tx, rule).await {
+        Ok(r) => db_tx.commit().await.map(|_| r),
+        Err(e) => Err(e),
+    };
+    rules.invalidate();
+    match res {
         Ok(r) => (
             StatusCode::OK,
             [(CONTENT_TYPE, "application/json")],
             serde_json::to_string(&r).unwrap(),
         ),
+        Err(e @ sqlx::Error::Protocol(_)) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+fn default_samples() -> usize {
+    10
+}
+
+#[derive(Deserialize)]
+pub struct RulePreviewRequest {
+    #[serde(default)]
+    regex: String,
+    /// Without a category every categorized match is counted as having another one.
+    category: Option<i32>,
+    #[serde(default = "default_match_all")]
+    match_all: bool,
+    #[serde(default)]
+    conditions: Vec<ConditionRequest>,
+    #[serde(default = "default_samples")]
+    samples: usize,
+}
+
+async fn preview_rule(pool: &SqlitePool, req: RulePreviewRequest) -> sqlx::Result<RulePreview> {
+    let mut conditions = Vec::new();
+    for c in req.conditions {
+        RuleCondition::validate(c.field, c.operator, &c.value).map_err(sqlx::Error::Protocol)?;
+        conditions.push(RuleCondition {
+            condition_id: 0,
+            rule: 0,
+            field: c.field,
+            operator: c.operator,
+            value: c.value,
+        });
+    }
+
+    let draft = Rule {
+        rule_id: 0,
+        regex: req.regex,
+        category: req.category.unwrap_or_default(),
+        priority: 0,
+        match_all: req.match_all,
+        enabled: true,
+        conditions,
+        actions: Vec::new(),
+    };
+    RulePreview::new(pool, draft, req.samples).await
+}
+
+pub async fn preview(
+    State(db): State<Arc<SqlitePool>>,
+    Json(req): Json<RulePreviewRequest>,
+) -> impl IntoResponse {
+    match preview_rule(db.as_ref(), req).await {
+        Ok(p) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&p).unwrap(),
+        ),
+        Err(e @ sqlx::Error::Protocol(_)) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             [(CONTENT_TYPE, "text/plain")],
@@ -48,3 +178,242 @@ pub async fn list(State(db): State<Arc<SqlitePool>>) -> impl IntoResponse {
         ),
     }
 }
+
+#[derive(Deserialize)]
+pub struct RuleUpdateRequest {
+    regex: Option<String>,
+    category: Option<i32>,
+    match_all: Option<bool>,
+    enabled: Option<bool>,
+}
+
+async fn update_rule(
+    conn: &mut SqliteConnection,
+    rule: &mut Rule,
+    req: RuleUpdateRequest,
+) -> sqlx::Result<()> {
+    if let Some(regex) = req.regex {
+        rule.set_regex(&mut *conn, regex).await?;
+    }
+    if let Some(category) = req.category {
+        rule.set_category(&mut *conn, category).await?;
+    }
+    if let Some(match_all) = req.match_all {
+        rule.set_match_all(&mut *conn, match_all).await?;
+    }
+    if let Some(enabled) = req.enabled {
+        rule.set_enabled(&mut *conn, enabled).await?;
+    }
+    Ok(())
+}
+
+pub async fn update(
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+    Json(req): Json<RuleUpdateRequest>,
+) -> (StatusCode, String) {
+    let mut rule = match Rule::get_by_id(&mut *db_tx, id).await {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    let res = match update_rule(&mut db_tx, &mut rule, req).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    rules.invalidate();
+    match res {
+        Ok(_) => (StatusCode::OK, serde_json::to_string(&rule).unwrap()),
+        Err(e @ sqlx::Error::Protocol(_)) => (StatusCode::BAD_REQUEST, format!("{e}")),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
+    }
+}
+
+/// Enables a disabled rule, or disables an enabled one.
+pub async fn toggle(
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+) -> (StatusCode, String) {
+    let mut rule = match Rule::get_by_id(&mut *db_tx, id).await {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    let res = match rule.set_enabled(&mut *db_tx, !rule.enabled).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    rules.invalidate();
+    match res {
+        Ok(_) => (StatusCode::OK, serde_json::to_string(&rule).unwrap()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
+    }
+}
+
+pub async fn delete(
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+) -> (StatusCode, String) {
+    let rule = match Rule::get_by_id(&mut *db_tx, id).await {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    let res = match rule.delete(&mut *db_tx).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    rules.invalidate();
+    match res {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RuleMoveRequest {
+    direction: MoveDirection,
+}
+
+pub async fn move_in_order(
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+    Json(req): Json<RuleMoveRequest>,
+) -> (StatusCode, String) {
+    let mut rule = match Rule::get_by_id(&mut *db_tx, id).await {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    let res = match rule.move_in_order(&mut *db_tx, req.direction).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    rules.invalidate();
+    match res {
+        Ok(_) => (StatusCode::OK, serde_json::to_string(&rule).unwrap()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
+    }
+}
+
+pub async fn condition_create(
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+    Json(req): Json<ConditionRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = Rule::get_by_id(&mut *db_tx, id).await {
+        return (
+            StatusCode::NOT_FOUND,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        );
+    }
+
+    let res = match RuleCondition::new(&mut *db_tx, id, req.field, req.operator, &req.value).await {
+        Ok(x) => db_tx.commit().await.map(|_| x),
+        Err(e) => Err(e),
+    };
+    rules.invalidate();
+    match res {
+        Ok(c) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&c).unwrap(),
+        ),
+        Err(e @ sqlx::Error::Protocol(_)) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn condition_delete(
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+) -> (StatusCode, String) {
+    let condition = match RuleCondition::get_by_id(&mut *db_tx, id).await {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    let res = match condition.delete(&mut *db_tx).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    rules.invalidate();
+    match res {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
+    }
+}
+
+pub async fn action_create(
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+    Json(req): Json<ActionRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = Rule::get_by_id(&mut *db_tx, id).await {
+        return (
+            StatusCode::NOT_FOUND,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        );
+    }
+
+    let res = match RuleAction::new(&mut *db_tx, id, req.action, &req.value).await {
+        Ok(x) => db_tx.commit().await.map(|_| x),
+        Err(e) => Err(e),
+    };
+    rules.invalidate();
+    match res {
+        Ok(a) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&a).unwrap(),
+        ),
+        Err(e @ sqlx::Error::Protocol(_)) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e}"),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+pub async fn action_delete(
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+) -> (StatusCode, String) {
+    let action = match RuleAction::get_by_id(&mut *db_tx, id).await {
+        Ok(a) => a,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    let res = match action.delete(&mut *db_tx).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    rules.invalidate();
+    match res {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Json, Path, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use accounters::models::{
+    money::Money,
+    scheduled::{Schedule, ScheduledTransaction},
+};
+
+use super::AuditTx;
+
+#[derive(Deserialize)]
+pub struct ScheduledCreateRequest {
+    account: i32,
+    description: String,
+    category: Option<i32>,
+    amount: Money,
+    schedule: Schedule,
+    start: NaiveDate,
+    end_date: Option<NaiveDate>,
+}
+
+pub async fn create(
+    State(db): State<Arc<SqlitePool>>,
+    Json(req): Json<ScheduledCreateRequest>,
+) -> impl IntoResponse {
+    let created = ScheduledTransaction::new(
+        db.as_ref(),
+        req.account,
+        &req.description,
+        req.category,
+        req.amount,
+        req.schedule,
+        req.start,
+    )
+    .await;
+
+    let mut scheduled = match created {
+        Ok(s) => s,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e:?}"),
+            )
+        }
+    };
+
+    if req.end_date.is_some() {
+        if let Err(e) = scheduled.set_end_date(db.as_ref(), req.end_date).await {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("{e:?}"),
+            );
+        }
+    }
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "application/json")],
+        serde_json::to_string(&scheduled).unwrap(),
+    )
+}
+
+pub async fn list(State(db): State<Arc<SqlitePool>>) -> impl IntoResponse {
+    match ScheduledTransaction::list(db.as_ref()).await {
+        Ok(s) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&s).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
+
+/// Removes the schedule. The transactions it created are kept, no longer linked to it.
+pub async fn delete(
+    State(db): State<Arc<SqlitePool>>,
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let scheduled = match ScheduledTransaction::get_by_id(db.as_ref(), id).await {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e:?}")),
+    };
+
+    let res = match scheduled.delete(&mut *db_tx).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    match res {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+    }
+}
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use accounters::models::subscriptions::Subscription;
+
+#[derive(Deserialize)]
+pub struct SubscriptionListParams {
+    account: Option<i32>,
+}
+
+pub async fn list(
+    State(db): State<Arc<SqlitePool>>,
+    Query(params): Query<SubscriptionListParams>,
+) -> impl IntoResponse {
+    match Subscription::list(db.as_ref(), params.account, Utc::now().date_naive()).await {
+        Ok(s) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&s).unwrap(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            format!("{e:?}"),
+        ),
+    }
+}
@@ -1,37 +1,160 @@
 use std::sync::Arc;
 
 use axum::extract::{Json, Path, Query, State};
-use chrono::{offset::Utc, DateTime};
+use chrono::{offset::Utc, DateTime, NaiveDate};
 use hyper::StatusCode;
 use serde::Deserialize;
-use sqlx::SqlitePool;
+use sqlx::{SqliteConnection, SqlitePool};
 
-use accounters::models::transaction::Transaction;
+use accounters::models::{
+    account::Account,
+    categories::Category,
+    money::Money,
+    rule_engine::RuleCache,
+    transaction::{MoveDirection, NewTransaction, Transaction, TransactionStatus},
+};
+
+use super::AuditTx;
 
 #[derive(Deserialize)]
 pub struct TransactionContent {
     description: String,
-    timestamp: DateTime<Utc>,
+    #[serde(default)]
+    payee: Option<String>,
+    /// Booking date. Requests written before it existed only send the timestamp, which
+    /// gives the date then.
+    #[serde(default)]
+    date: Option<NaiveDate>,
+    #[serde(default)]
+    value_date: Option<NaiveDate>,
+    #[serde(default)]
+    timestamp: Option<DateTime<Utc>>,
+    /// Name of the category. Without it the rules are applied, if the account allows it.
+    #[serde(default)]
     category: Option<String>,
-    amount: i32,
+    /// Decimal string like `"-12.34"`.
+    amount: Money,
+    #[serde(default)]
+    status: TransactionStatus,
+}
+
+async fn create_transaction(
+    pool: &SqlitePool,
+    conn: &mut SqliteConnection,
+    rules: &RuleCache,
+    account: &Account,
+    txcnt: TransactionContent,
+) -> sqlx::Result<Transaction> {
+    let category = match txcnt.category.as_deref().map(str::trim) {
+        Some(name) if !name.is_empty() => match Category::get_by_name(&mut *conn, name).await? {
+            Some(c) => Some(c.category_id),
+            None => return Err(sqlx::Error::Protocol(format!("Unknown category {name:?}"))),
+        },
+        _ => None,
+    };
+
+    let date = txcnt
+        .date
+        .or(txcnt.timestamp.map(|t| t.date_naive()))
+        .ok_or_else(|| sqlx::Error::Protocol("Either date or timestamp is required".to_string()))?;
+
+    let mut tx = Transaction::insert(
+        &mut *conn,
+        &NewTransaction {
+            account: account.get_id(),
+            description: &txcnt.description,
+            date,
+            category,
+            amount: txcnt.amount,
+            status: txcnt.status,
+            payee: txcnt.payee.as_deref(),
+            value_date: txcnt.value_date,
+            timestamp: txcnt.timestamp,
+            scheduled: None,
+        },
+    )
+    .await?;
+
+    if category.is_none() {
+        let engine = rules.get(pool).await?;
+        if account
+            .classify_new(&mut *conn, &engine, std::slice::from_ref(&tx))
+            .await?
+            > 0
+        {
+            tx = Transaction::get_by_id(&mut *conn, tx.get_id()).await?;
+        }
+    }
+    Ok(tx)
 }
 
+/// Stores a transaction in the account.
+///
+/// `amount` used to be an integer number of cents and is now a decimal string. A JSON
+/// number is answered with 422 rather than guessed, so a client still sending `1234`
+/// for 12.34 fails loudly instead of booking 1234.00.
 pub async fn create(
     State(db): State<Arc<SqlitePool>>,
+    State(rules): State<Arc<RuleCache>>,
+    mut db_tx: AuditTx,
     Path(account): Path<i32>,
     Json(txcnt): Json<TransactionContent>,
 ) -> (StatusCode, String) {
-    match Transaction::new(
-        db.as_ref(),
-        account,
-        &txcnt.description,
-        &txcnt.timestamp,
-        None,
-        txcnt.amount,
-    )
-    .await
-    {
+    let account = match Account::get_by_id(&mut *db_tx, account).await {
+        Ok(a) => a,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    // The transaction is stored together with the category given by the rules.
+    let res =
+        match create_transaction(db.as_ref(), &mut db_tx, rules.as_ref(), &account, txcnt).await {
+            Ok(tx) => db_tx.commit().await.map(|_| tx),
+            Err(e) => Err(e),
+        };
+    match res {
         Ok(tx) => (StatusCode::OK, serde_json::to_string(&tx).unwrap()),
+        Err(e @ sqlx::Error::Protocol(_)) => (StatusCode::BAD_REQUEST, format!("{e}")),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
+    }
+}
+
+pub async fn delete(mut db_tx: AuditTx, Path(id): Path<i32>) -> (StatusCode, String) {
+    let tx = match Transaction::get_by_id(&mut *db_tx, id).await {
+        Ok(tx) => tx,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    let res = match tx.delete(&mut *db_tx).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    match res {
+        Ok(_) => (StatusCode::OK, String::new()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TransactionMoveRequest {
+    direction: MoveDirection,
+}
+
+pub async fn move_within_day(
+    mut db_tx: AuditTx,
+    Path(id): Path<i32>,
+    Json(req): Json<TransactionMoveRequest>,
+) -> (StatusCode, String) {
+    let mut tx = match Transaction::get_by_id(&mut *db_tx, id).await {
+        Ok(tx) => tx,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e}")),
+    };
+
+    let res = match tx.move_within_day(&mut *db_tx, req.direction).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    match res {
+        Ok(_) => (StatusCode::OK, serde_json::to_string(&tx).unwrap()),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")),
     }
 }
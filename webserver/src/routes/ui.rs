@@ -1,75 +1,143 @@
 use std::{borrow::BorrowMut, collections::HashMap, sync::Arc};
 
-use axum::{extract::State, response::IntoResponse};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
 use chrono::Utc;
 use hyper::{header::CONTENT_TYPE, StatusCode};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use tera::{Context, Tera};
 
-use accounters::models::{account::Account, categories::Category, transaction::Transaction};
+use accounters::models::{
+    account::{Account, AccountType},
+    budgets::Budget,
+    categories::Category,
+    money::Money,
+    transaction::Transaction,
+};
 
 pub mod account;
 pub mod classifier;
+pub mod goals;
+pub mod investments;
+pub mod loans;
+pub mod scheduled;
+pub mod subscriptions;
 pub mod transaction;
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 struct AccountRender {
     id: i32,
     description: String,
-    accumulated: f32,
+    account_type: AccountType,
+    liability: bool,
+    archived: bool,
+    accumulated: Money,
 }
 
 impl AccountRender {
     async fn from_account(pool: &SqlitePool, acc: Account) -> Self {
-        let last_acc = Transaction::list_by_account(pool, acc.get_id(), 1, 0, false)
-            .await
-            .map_or(0.0, |x| {
-                x.get(0)
-                    .map_or(0.0, |x| (x.get_accumulated() as f32) / 100.0)
-            });
+        let balance = acc.get_balance(pool).await.unwrap_or_default();
         Self {
             id: acc.get_id(),
             description: acc.get_account_name().to_string(),
-            accumulated: last_acc,
+            account_type: acc.get_account_type(),
+            liability: acc.is_liability(),
+            archived: acc.is_archived(),
+            accumulated: acc.display_balance(balance),
         }
     }
 }
 
-fn hm_sort(hm: HashMap<i32, i64>, collapse: usize) -> Vec<(i32, i64)> {
-    let mut res: Vec<(i32, i64)> = hm.into_iter().collect();
+/// Order in which the account types are listed.
+const ACCOUNT_TYPES: [AccountType; 6] = [
+    AccountType::Checking,
+    AccountType::Savings,
+    AccountType::CreditCard,
+    AccountType::Cash,
+    AccountType::Loan,
+    AccountType::Investment,
+];
+
+#[derive(Serialize)]
+struct AccountGroupRender {
+    account_type: AccountType,
+    liability: bool,
+    accounts: Vec<AccountRender>,
+    /// Balance of the accounts of the group that are not archived.
+    total: Money,
+}
+
+fn hm_sort(hm: HashMap<i32, Money>, collapse: usize) -> Vec<(i32, Money)> {
+    let mut res: Vec<(i32, Money)> = hm.into_iter().collect();
     res.sort_unstable_by(|a, b| b.1.cmp(&a.1));
     if res.len() > collapse {
         let rest = res
             .split_off(collapse)
             .iter()
-            .fold(0i64, |acc, item| acc + item.1);
+            .fold(Money::ZERO, |acc, item| acc + item.1);
         let last = res.last_mut().unwrap();
         *last = (-1, last.1 + rest);
     }
     res
 }
 
+#[derive(Deserialize)]
+pub struct IndexParams {
+    archived: Option<bool>,
+}
+
 pub async fn index(
     State(db): State<Arc<SqlitePool>>,
     State(tmpls): State<Arc<Tera>>,
+    Query(params): Query<IndexParams>,
 ) -> impl IntoResponse {
     let mut ctx = Context::new();
 
-    let accounts = Account::list(db.as_ref()).await.unwrap();
+    let show_archived = params.archived.unwrap_or(false);
+    ctx.insert("show_archived", &show_archived);
+
+    let accounts = Account::list(db.as_ref(), show_archived).await.unwrap();
     let mut acc_render = Vec::new();
 
     for acc in accounts.into_iter() {
         acc_render.push(AccountRender::from_account(db.as_ref(), acc).await);
     }
 
-    ctx.insert("accounts", &acc_render);
+    let net_worth = Account::net_worth(db.as_ref()).await.unwrap();
+    let account_groups: Vec<AccountGroupRender> = ACCOUNT_TYPES
+        .iter()
+        .map(|account_type| {
+            let accounts: Vec<AccountRender> = acc_render
+                .iter()
+                .filter(|x| x.account_type == *account_type)
+                .cloned()
+                .collect();
+            let total = accounts
+                .iter()
+                .filter(|x| !x.archived)
+                .map(|x| x.accumulated)
+                .sum();
+            AccountGroupRender {
+                account_type: *account_type,
+                liability: account_type.default_liability(),
+                accounts,
+                total,
+            }
+        })
+        .filter(|x| !x.accounts.is_empty())
+        .collect();
+    ctx.insert("account_groups", &account_groups);
+    ctx.insert("net_worth", &net_worth);
 
+    let today = Utc::now().date_naive();
     let last_month = Transaction::list_by_date(
         db.as_ref(),
         None,
-        Some(Utc::now() - chrono::Duration::days(30)),
-        Some(Utc::now()),
+        Some(today - chrono::Duration::days(30)),
+        Some(today + chrono::Duration::days(1)),
         None,
         false,
     )
@@ -85,22 +153,25 @@ pub async fn index(
     categories.insert(0, String::from("Unclassified"));
     ctx.insert("categories", &categories);
 
-    let mut income: HashMap<i32, i64> = HashMap::new();
-    let mut expenses: HashMap<i32, i64> = HashMap::new();
+    let budgets = Budget::list_status(db.as_ref(), today).await.unwrap();
+    ctx.insert("budgets", &budgets);
+
+    let mut income: HashMap<i32, Money> = HashMap::new();
+    let mut expenses: HashMap<i32, Money> = HashMap::new();
 
     for tx in last_month.iter() {
-        if tx.get_amount() > 0 {
+        if tx.get_amount().is_positive() {
             let acc = income
                 .entry(tx.get_category().unwrap_or(0))
                 .or_default()
                 .borrow_mut();
-            *acc = *acc + tx.get_amount() as i64;
+            *acc += tx.get_amount();
         } else {
             let acc = expenses
                 .entry(tx.get_category().unwrap_or(0))
                 .or_default()
                 .borrow_mut();
-            *acc = *acc - tx.get_amount() as i64;
+            *acc -= tx.get_amount();
         }
     }
 
@@ -5,13 +5,23 @@ use axum::{
     response::IntoResponse,
     Json,
 };
-use chrono::{Date, DateTime, Duration, DurationRound, TimeZone, Utc};
+use chrono::{Duration, NaiveDate, Utc};
 use hyper::{header::CONTENT_TYPE, StatusCode};
 use serde::Deserialize;
-use sqlx::SqlitePool;
+use sqlx::{SqliteConnection, SqlitePool};
 use tera::{Context, Tera};
 
-use accounters::models::{account::Account, categories::Category, transaction::Transaction};
+use accounters::models::{
+    account::{Account, AccountType},
+    categories::Category,
+    forecast::{Forecast, DEFAULT_FORECAST_DAYS},
+    investments::{Portfolio, Security},
+    loans::Loan,
+    money::Money,
+    rule_engine::RuleCache,
+    scheduled::SCHEDULE_MATCH_DAYS,
+    transaction::{NewTransaction, Transaction, TransactionStatus},
+};
 
 #[derive(Deserialize)]
 pub struct AccountViewParams {
@@ -19,12 +29,12 @@ pub struct AccountViewParams {
     to: Option<String>,
 }
 
-fn parse_date(s: &str) -> Option<DateTime<Utc>> {
+fn parse_date(s: &str) -> Option<NaiveDate> {
     let mut iter = s.split('-');
     let year = iter.next()?.parse::<i32>().ok()?;
     let month = iter.next()?.parse::<u32>().ok()?;
     let day = iter.next()?.parse::<u32>().ok()?;
-    Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).single()
+    NaiveDate::from_ymd_opt(year, month, day)
 }
 
 pub async fn show(
@@ -46,12 +56,11 @@ pub async fn show(
         }
     };
 
+    let today = Utc::now().date_naive();
     let from = from
         .and_then(|x| parse_date(&x))
-        .unwrap_or(Utc::now().duration_trunc(Duration::days(1)).unwrap() - Duration::days(30));
-    let to = to
-        .and_then(|x| parse_date(&x))
-        .unwrap_or(Utc::now().duration_trunc(Duration::days(1)).unwrap());
+        .unwrap_or(today - Duration::days(30));
+    let to = to.and_then(|x| parse_date(&x)).unwrap_or(today);
 
     ctx.insert("date_from", &from);
     ctx.insert("date_to", &to);
@@ -82,8 +91,55 @@ pub async fn show(
         }
     };
 
+    let balances = match account.get_balances(db.as_ref()).await {
+        Ok(b) => b,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("Error at loading balances: {e}"),
+            );
+        }
+    };
+
+    let forecast = match Forecast::for_account(
+        db.as_ref(),
+        &account,
+        Utc::now().date_naive(),
+        DEFAULT_FORECAST_DAYS,
+    )
+    .await
+    {
+        Ok(f) => f,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain")],
+                format!("Error at computing the forecast: {e}"),
+            );
+        }
+    };
+
+    if account.get_account_type() == AccountType::Investment {
+        let portfolio =
+            match Portfolio::for_account(db.as_ref(), account_id, Utc::now().date_naive()).await {
+                Ok(p) => p,
+                Err(e) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        [(CONTENT_TYPE, "text/plain")],
+                        format!("Error at loading holdings: {e}"),
+                    );
+                }
+            };
+        ctx.insert("portfolio", &portfolio);
+        ctx.insert("securities", &Security::list(db.as_ref()).await.unwrap());
+    }
+
     ctx.insert("account", &account);
+    ctx.insert("balances", &balances);
     ctx.insert("transactions", &txs);
+    ctx.insert("forecast", &forecast);
     (
         StatusCode::OK,
         [(CONTENT_TYPE, "text/html;charset=utf-8")],
@@ -190,29 +246,143 @@ pub async fn add_transactions_view(
 
 #[derive(Deserialize, Debug)]
 pub struct CreateTransactionRequest {
-    date: DateTime<Utc>,
+    date: NaiveDate,
+    #[serde(default)]
+    value_date: Option<NaiveDate>,
     description: String,
-    amount: f32,
+    #[serde(default)]
+    payee: Option<String>,
+    amount: Money,
+    /// Name of the category. Entries that have one are not classified by the rules.
+    #[serde(default)]
+    category: Option<String>,
+}
+
+async fn confirm_scheduled(
+    conn: &mut SqliteConnection,
+    pending: &mut Transaction,
+    entry: &CreateTransactionRequest,
+    category: Option<i32>,
+) -> sqlx::Result<()> {
+    if pending.get_date() != entry.date {
+        pending.set_date(&mut *conn, entry.date).await?;
+    }
+    if entry.value_date.is_some() && pending.get_value_date() != entry.value_date {
+        pending.set_value_date(&mut *conn, entry.value_date).await?;
+    }
+    if pending.get_description() != entry.description {
+        pending
+            .set_description(&mut *conn, &entry.description)
+            .await?;
+    }
+    if entry.payee.is_some() && pending.get_payee() != entry.payee.as_deref() {
+        pending
+            .set_payee(&mut *conn, entry.payee.as_deref())
+            .await?;
+    }
+    if category.is_some() && pending.get_category() != category {
+        pending.set_category(&mut *conn, category).await?;
+    }
+    pending.set_status(conn, TransactionStatus::Cleared).await
 }
 
 pub async fn add_transactions_action(
     State(db): State<Arc<SqlitePool>>,
+    State(rules): State<Arc<RuleCache>>,
     Path(account_id): Path<i32>,
-    Json(body): Json<Vec<CreateTransactionRequest>>,
+    Json(mut body): Json<Vec<CreateTransactionRequest>>,
 ) -> impl IntoResponse {
+    let account = match Account::get_by_id(db.as_ref(), account_id).await {
+        Ok(a) => a,
+        Err(e) => return (StatusCode::NOT_FOUND, format!("{e:?}")),
+    };
+
+    // Statements usually list the newest movements first. Inserting them oldest first keeps
+    // the order of the file for transactions that share a date.
+    if body.first().map(|x| x.date) > body.last().map(|x| x.date) {
+        body.reverse();
+    }
+    body.sort_by_key(|x| x.date);
+
+    let engine = match rules.get(db.as_ref()).await {
+        Ok(engine) => engine,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+    };
+
+    // The statement is stored and classified as a whole, or not at all.
+    let mut db_tx = match db.begin().await {
+        Ok(t) => t,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+    };
+    let mut created = Vec::new();
     for tx in body.iter() {
-        if let Err(e) = Transaction::new(
-            db.as_ref(),
+        let category = match tx.category.as_deref() {
+            Some(name) => match Category::get_by_name(&mut *db_tx, name).await {
+                Ok(Some(c)) => Some(c.category_id),
+                Ok(None) => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        format!("Unknown category {name:?}"),
+                    )
+                }
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+            },
+            None => None,
+        };
+
+        // A statement entry confirms a pending transaction created from a schedule
+        // instead of adding it a second time.
+        let scheduled = match Transaction::find_scheduled_match(
+            &mut *db_tx,
             account_id,
-            &tx.description,
-            &tx.date,
-            None,
-            (tx.amount * 100.0).round() as i32,
+            tx.date,
+            tx.amount,
+            SCHEDULE_MATCH_DAYS,
         )
         .await
         {
+            Ok(s) => s,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")),
+        };
+
+        let res = match scheduled {
+            Some(mut pending) => confirm_scheduled(&mut db_tx, &mut pending, tx, category).await,
+            None => Transaction::insert(
+                &mut *db_tx,
+                &NewTransaction {
+                    account: account_id,
+                    description: &tx.description,
+                    date: tx.date,
+                    category,
+                    amount: tx.amount,
+                    status: TransactionStatus::Cleared,
+                    payee: tx.payee.as_deref(),
+                    value_date: tx.value_date,
+                    timestamp: None,
+                    scheduled: None,
+                },
+            )
+            .await
+            .map(|t| created.push(t)),
+        };
+
+        if let Err(e) = res {
             return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}"));
         }
     }
+
+    // The rules are applied to the whole statement at once, after it is stored. Entries
+    // that came with a category are left as they are.
+    let classified = match account.classify_new(&mut *db_tx, &engine, &created).await {
+        Ok(_) => db_tx.commit().await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = classified {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}"));
+    }
+
+    if let Err(e) = Loan::match_account_payments(db.as_ref(), account_id).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}"));
+    }
     (StatusCode::OK, String::new())
 }
@@ -1,8 +1,12 @@
 use std::sync::Arc;
 
-use accounters::models::{categories::Category, rules::Rule};
+use accounters::models::{
+    categories::Category,
+    rule_engine::RuleCache,
+    rules::{ActionType, ConditionField, ConditionOperator, Rule, RuleAction, RuleCondition},
+};
 use axum::{
-    extract::{Form, State},
+    extract::{Form, Path, State},
     response::IntoResponse,
 };
 use hyper::{
@@ -70,18 +74,52 @@ pub struct NewRuleParams {
     pub description: String,
     pub regex: String,
     pub category: i32,
+    pub match_all: bool,
+    /// Conditions of the draft as a JSON list, filled in by the form when submitted.
+    #[serde(default)]
+    pub conditions: String,
+}
+
+async fn create_rule(pool: &SqlitePool, params: NewRuleParams) -> sqlx::Result<()> {
+    let conditions: Vec<NewConditionParams> = if params.conditions.is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_str(&params.conditions)
+            .map_err(|e| sqlx::Error::Protocol(format!("Invalid conditions: {e}")))?
+    };
+    for c in conditions.iter() {
+        RuleCondition::validate(c.field, c.operator, &c.value).map_err(sqlx::Error::Protocol)?;
+    }
+
+    let mut rule = Rule::new(pool, params.regex, params.category).await?;
+    if !params.match_all {
+        rule.set_match_all(pool, false).await?;
+    }
+    for c in conditions.iter() {
+        RuleCondition::new(pool, rule.rule_id, c.field, c.operator, &c.value).await?;
+    }
+    Ok(())
 }
 
 pub async fn rules_new_action(
     State(db): State<Arc<SqlitePool>>,
+    State(rules): State<Arc<RuleCache>>,
     Form(params): Form<NewRuleParams>,
 ) -> impl IntoResponse {
-    match Rule::new(db.as_ref(), params.regex, params.category).await {
+    let res = create_rule(db.as_ref(), params).await;
+    rules.invalidate();
+
+    match res {
         Ok(_) => (
             StatusCode::MOVED_PERMANENTLY,
             [(LOCATION, "/classifiers")],
             String::new(),
         ),
+        Err(e @ sqlx::Error::Protocol(_)) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+            format!("{e}"),
+        ),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             [(CONTENT_TYPE, "text/plain;charset=utf-8")],
@@ -90,6 +128,71 @@ pub async fn rules_new_action(
     }
 }
 
+#[derive(Deserialize)]
+pub struct NewConditionParams {
+    pub field: ConditionField,
+    pub operator: ConditionOperator,
+    pub value: String,
+}
+
+pub async fn condition_new_action(
+    State(db): State<Arc<SqlitePool>>,
+    State(rules): State<Arc<RuleCache>>,
+    Path(rule): Path<i32>,
+    Form(params): Form<NewConditionParams>,
+) -> impl IntoResponse {
+    let res = RuleCondition::new(
+        db.as_ref(),
+        rule,
+        params.field,
+        params.operator,
+        &params.value,
+    )
+    .await;
+    rules.invalidate();
+    match res {
+        Ok(_) => (
+            StatusCode::MOVED_PERMANENTLY,
+            [(LOCATION, "/classifiers")],
+            String::new(),
+        ),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+            format!("{e}"),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewActionParams {
+    pub action: ActionType,
+    #[serde(default)]
+    pub value: String,
+}
+
+pub async fn action_new_action(
+    State(db): State<Arc<SqlitePool>>,
+    State(rules): State<Arc<RuleCache>>,
+    Path(rule): Path<i32>,
+    Form(params): Form<NewActionParams>,
+) -> impl IntoResponse {
+    let res = RuleAction::new(db.as_ref(), rule, params.action, &params.value).await;
+    rules.invalidate();
+    match res {
+        Ok(_) => (
+            StatusCode::MOVED_PERMANENTLY,
+            [(LOCATION, "/classifiers")],
+            String::new(),
+        ),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+            format!("{e}"),
+        ),
+    }
+}
+
 pub async fn category_new_view(State(tmpl): State<Arc<Tera>>) -> impl IntoResponse {
     (
         StatusCode::OK,
@@ -0,0 +1,86 @@
+use std::{collections::HashMap, sync::Arc};
+
+use accounters::models::{account::Account, goals::Goal, money::Money};
+use axum::{
+    extract::{Form, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use hyper::{
+    header::{CONTENT_TYPE, LOCATION},
+    StatusCode,
+};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use tera::{Context, Tera};
+
+pub async fn view(
+    State(db): State<Arc<SqlitePool>>,
+    State(tmpls): State<Arc<Tera>>,
+) -> impl IntoResponse {
+    let goals = match Goal::list_progress(db.as_ref()).await {
+        Ok(g) => g,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+                format!("{e}"),
+            )
+        }
+    };
+
+    let accounts = Account::list(db.as_ref(), false).await.unwrap();
+    let account_names: HashMap<i32, String> = Account::list(db.as_ref(), true)
+        .await
+        .unwrap()
+        .iter()
+        .map(|x| (x.get_id(), x.get_account_name().to_string()))
+        .collect();
+
+    let mut ctx = Context::new();
+    ctx.insert("goals", &goals);
+    ctx.insert("accounts", &accounts);
+    ctx.insert("account_names", &account_names);
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/html;charset=utf-8")],
+        tmpls.render("goals.html", &ctx).unwrap(),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct NewGoalParams {
+    pub name: String,
+    pub account: i32,
+    pub share: i32,
+    pub target_amount: Money,
+    pub target_date: NaiveDate,
+}
+
+pub async fn new_action(
+    State(db): State<Arc<SqlitePool>>,
+    Form(params): Form<NewGoalParams>,
+) -> impl IntoResponse {
+    match Goal::new(
+        db.as_ref(),
+        &params.name,
+        params.account,
+        params.share,
+        params.target_amount,
+        params.target_date,
+    )
+    .await
+    {
+        Ok(_) => (
+            StatusCode::MOVED_PERMANENTLY,
+            [(LOCATION, "/goals")],
+            String::new(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+            format!("{e}"),
+        ),
+    }
+}
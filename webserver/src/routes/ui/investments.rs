@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use accounters::models::{
+    investments::{Trade, TradeKind},
+    money::Money,
+};
+use axum::{
+    extract::{Form, Path, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use hyper::{
+    header::{CONTENT_TYPE, LOCATION},
+    StatusCode,
+};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+#[derive(Deserialize)]
+pub struct NewTradeParams {
+    pub security: i32,
+    pub date: NaiveDate,
+    pub kind: TradeKind,
+    pub quantity: f64,
+    /// Price per unit for buys and sales, total received for dividends.
+    pub price: Money,
+    pub fees: Money,
+}
+
+pub async fn new_trade_action(
+    State(db): State<Arc<SqlitePool>>,
+    Path(account): Path<i32>,
+    Form(params): Form<NewTradeParams>,
+) -> impl IntoResponse {
+    let trade = match params.kind {
+        TradeKind::Dividend => {
+            Trade::dividend(
+                db.as_ref(),
+                account,
+                params.security,
+                params.date,
+                params.price,
+            )
+            .await
+        }
+        kind => {
+            Trade::new(
+                db.as_ref(),
+                account,
+                params.security,
+                params.date,
+                kind,
+                params.quantity,
+                params.price,
+                params.fees,
+            )
+            .await
+        }
+    };
+
+    match trade {
+        Ok(_) => (
+            StatusCode::MOVED_PERMANENTLY,
+            [(LOCATION, format!("/accounts/id/{account}"))],
+            String::new(),
+        ),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, String::from("text/plain;charset=utf-8"))],
+            format!("{e}"),
+        ),
+    }
+}
@@ -0,0 +1,166 @@
+use std::{collections::HashMap, sync::Arc};
+
+use accounters::models::{account::Account, loans::Loan, money::Money};
+use axum::{
+    extract::{Form, Path, State},
+    response::IntoResponse,
+};
+use chrono::{NaiveDate, Utc};
+use hyper::{
+    header::{CONTENT_TYPE, LOCATION},
+    StatusCode,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::SqlitePool;
+use tera::{Context, Tera};
+
+#[derive(Serialize)]
+struct LoanRender {
+    #[serde(flatten)]
+    loan: Loan,
+    monthly_payment: Money,
+    outstanding: Money,
+}
+
+pub async fn list_view(
+    State(db): State<Arc<SqlitePool>>,
+    State(tmpls): State<Arc<Tera>>,
+) -> impl IntoResponse {
+    let loans = match Loan::list(db.as_ref()).await {
+        Ok(l) => l,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+                format!("{e}"),
+            )
+        }
+    };
+
+    let today = Utc::now().date_naive();
+    let loans: Vec<LoanRender> = loans
+        .into_iter()
+        .map(|loan| LoanRender {
+            monthly_payment: loan.monthly_payment(),
+            outstanding: Loan::balance_at(&loan.amortization(), loan.get_principal(), today),
+            loan,
+        })
+        .collect();
+
+    let accounts = Account::list(db.as_ref(), false).await.unwrap();
+    let account_names: HashMap<i32, String> = Account::list(db.as_ref(), true)
+        .await
+        .unwrap()
+        .iter()
+        .map(|x| (x.get_id(), x.get_account_name().to_string()))
+        .collect();
+
+    let mut ctx = Context::new();
+    ctx.insert("loans", &loans);
+    ctx.insert("accounts", &accounts);
+    ctx.insert("account_names", &account_names);
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/html;charset=utf-8")],
+        tmpls.render("loans.html", &ctx).unwrap(),
+    )
+}
+
+pub async fn view(
+    State(db): State<Arc<SqlitePool>>,
+    State(tmpls): State<Arc<Tera>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let loan = match Loan::get_by_id(db.as_ref(), id).await {
+        Ok(l) => l,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+                format!("{e}"),
+            )
+        }
+    };
+    let schedule = loan.schedule(db.as_ref()).await.unwrap();
+    let account = Account::get_by_id(db.as_ref(), loan.get_account())
+        .await
+        .unwrap();
+
+    let mut ctx = Context::new();
+    ctx.insert("account", &account);
+    ctx.insert("monthly_payment", &loan.monthly_payment());
+    ctx.insert(
+        "outstanding",
+        &Loan::balance_at(&schedule, loan.get_principal(), Utc::now().date_naive()),
+    );
+    ctx.insert("schedule", &schedule);
+    ctx.insert("loan", &loan);
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/html;charset=utf-8")],
+        tmpls.render("loan.html", &ctx).unwrap(),
+    )
+}
+
+fn deserialize_optional<'de, D>(data: D) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let str = String::deserialize(data)?;
+    if str.is_empty() {
+        Ok(None)
+    } else {
+        str.parse().map(Some).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewLoanParams {
+    pub account: i32,
+    pub principal: Money,
+    pub annual_rate: f64,
+    pub term_months: i32,
+    pub start_date: NaiveDate,
+    #[serde(deserialize_with = "deserialize_optional")]
+    pub payment_account: Option<i32>,
+}
+
+pub async fn new_action(
+    State(db): State<Arc<SqlitePool>>,
+    Form(params): Form<NewLoanParams>,
+) -> impl IntoResponse {
+    let created = Loan::new(
+        db.as_ref(),
+        params.account,
+        params.principal,
+        params.annual_rate,
+        params.term_months,
+        params.start_date,
+        params.payment_account,
+    )
+    .await;
+
+    match created {
+        Ok(loan) => {
+            if let Err(e) = loan.match_payments(db.as_ref()).await {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    [(CONTENT_TYPE, String::from("text/plain;charset=utf-8"))],
+                    format!("{e}"),
+                );
+            }
+            (
+                StatusCode::MOVED_PERMANENTLY,
+                [(LOCATION, format!("/loans/id/{}", loan.get_id()))],
+                String::new(),
+            )
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            [(CONTENT_TYPE, String::from("text/plain;charset=utf-8"))],
+            format!("{e}"),
+        ),
+    }
+}
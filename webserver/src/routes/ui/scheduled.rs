@@ -0,0 +1,113 @@
+use std::{collections::HashMap, sync::Arc};
+
+use accounters::models::{
+    account::Account,
+    categories::Category,
+    money::Money,
+    scheduled::{Schedule, ScheduledTransaction},
+};
+use axum::{
+    extract::{Form, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use hyper::{
+    header::{CONTENT_TYPE, LOCATION},
+    StatusCode,
+};
+use serde::{Deserialize, Deserializer};
+use sqlx::SqlitePool;
+use tera::{Context, Tera};
+
+pub async fn view(
+    State(db): State<Arc<SqlitePool>>,
+    State(tmpls): State<Arc<Tera>>,
+) -> impl IntoResponse {
+    let scheduled = match ScheduledTransaction::list(db.as_ref()).await {
+        Ok(s) => s,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+                format!("{e}"),
+            )
+        }
+    };
+
+    let accounts = Account::list(db.as_ref(), false).await.unwrap();
+    let account_names: HashMap<i32, String> = Account::list(db.as_ref(), true)
+        .await
+        .unwrap()
+        .iter()
+        .map(|x| (x.get_id(), x.get_account_name().to_string()))
+        .collect();
+    let categories = Category::list(db.as_ref()).await.unwrap();
+    let category_names: HashMap<i32, String> = categories
+        .iter()
+        .map(|x| (x.category_id, x.name.clone()))
+        .collect();
+
+    let mut ctx = Context::new();
+    ctx.insert("scheduled", &scheduled);
+    ctx.insert("accounts", &accounts);
+    ctx.insert("account_names", &account_names);
+    ctx.insert("categories", &categories);
+    ctx.insert("category_names", &category_names);
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/html;charset=utf-8")],
+        tmpls.render("scheduled.html", &ctx).unwrap(),
+    )
+}
+
+fn deserialize_optional<'de, D>(data: D) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let str = String::deserialize(data)?;
+    if str.is_empty() {
+        Ok(None)
+    } else {
+        str.parse().map(Some).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewScheduledParams {
+    pub account: i32,
+    pub description: String,
+    #[serde(deserialize_with = "deserialize_optional")]
+    pub category: Option<i32>,
+    pub amount: Money,
+    pub schedule: Schedule,
+    pub start: NaiveDate,
+}
+
+pub async fn new_action(
+    State(db): State<Arc<SqlitePool>>,
+    Form(params): Form<NewScheduledParams>,
+) -> impl IntoResponse {
+    match ScheduledTransaction::new(
+        db.as_ref(),
+        params.account,
+        &params.description,
+        params.category,
+        params.amount,
+        params.schedule,
+        params.start,
+    )
+    .await
+    {
+        Ok(_) => (
+            StatusCode::MOVED_PERMANENTLY,
+            [(LOCATION, "/scheduled")],
+            String::new(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+            format!("{e}"),
+        ),
+    }
+}
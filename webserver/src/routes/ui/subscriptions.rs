@@ -0,0 +1,45 @@
+use std::{collections::HashMap, sync::Arc};
+
+use accounters::models::{account::Account, subscriptions::Subscription};
+use axum::{extract::State, response::IntoResponse};
+use chrono::Utc;
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use sqlx::SqlitePool;
+use tera::{Context, Tera};
+
+pub async fn view(
+    State(db): State<Arc<SqlitePool>>,
+    State(tmpls): State<Arc<Tera>>,
+) -> impl IntoResponse {
+    let subscriptions = match Subscription::list(db.as_ref(), None, Utc::now().date_naive()).await {
+        Ok(s) => s,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(CONTENT_TYPE, "text/plain;charset=utf-8")],
+                format!("{e}"),
+            )
+        }
+    };
+
+    let accounts: HashMap<i32, String> = Account::list(db.as_ref(), true)
+        .await
+        .unwrap()
+        .iter()
+        .map(|x| (x.get_id(), x.get_account_name().to_string()))
+        .collect();
+
+    let (active, stopped): (Vec<Subscription>, Vec<Subscription>) =
+        subscriptions.into_iter().partition(|x| x.active);
+
+    let mut ctx = Context::new();
+    ctx.insert("active", &active);
+    ctx.insert("stopped", &stopped);
+    ctx.insert("accounts", &accounts);
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/html;charset=utf-8")],
+        tmpls.render("subscriptions.html", &ctx).unwrap(),
+    )
+}
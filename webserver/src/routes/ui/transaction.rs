@@ -1,17 +1,42 @@
-use std::sync::Arc;
+use std::{fmt::Display, str::FromStr, sync::Arc};
 
-use accounters::models::{categories::Category, transaction::Transaction};
+use accounters::models::{
+    account::Account,
+    audit::{AuditAction, AuditEntry, FieldChange},
+    categories::Category,
+    money::Money,
+    transaction::{Transaction, TransactionStatus},
+};
 use axum::{
     extract::{Path, State},
     response::IntoResponse,
     Form,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use hyper::{header, StatusCode};
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::SqlitePool;
 use tera::Tera;
 
+#[derive(Serialize)]
+struct HistoryRender {
+    action: AuditAction,
+    user: Option<i32>,
+    changed_at: DateTime<Utc>,
+    changes: Vec<FieldChange>,
+}
+
+impl From<AuditEntry> for HistoryRender {
+    fn from(entry: AuditEntry) -> Self {
+        Self {
+            action: entry.get_action(),
+            user: entry.get_user(),
+            changed_at: *entry.get_changed_at(),
+            changes: entry.changes(),
+        }
+    }
+}
+
 pub async fn view(
     db: State<Arc<SqlitePool>>,
     tmpl: State<Arc<Tera>>,
@@ -25,6 +50,18 @@ pub async fn view(
     let categories = Category::list(db.as_ref()).await.unwrap();
     ctx.insert("categories", &categories);
 
+    let accounts = Account::list(db.as_ref(), true).await.unwrap();
+    ctx.insert("accounts", &accounts);
+    ctx.insert("tags", &tx.get_tags(db.as_ref()).await.unwrap().join(", "));
+
+    let history: Vec<HistoryRender> = AuditEntry::list_for_row(db.as_ref(), "transactions", id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(HistoryRender::from)
+        .collect();
+    ctx.insert("history", &history);
+
     (
         StatusCode::OK,
         [(header::CONTENT_TYPE, "text/html;charset=utf-8")],
@@ -32,25 +69,100 @@ pub async fn view(
     )
 }
 
-fn deserialize_optional<'de, D>(data: D) -> Result<Option<i32>, D::Error>
+fn deserialize_optional<'de, D, T>(data: D) -> Result<Option<T>, D::Error>
 where
     D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
 {
     let str = String::deserialize(data)?;
     if str.is_empty() {
         Ok(None)
     } else {
-        Ok(Some(str.parse().unwrap()))
+        str.parse().map(Some).map_err(serde::de::Error::custom)
     }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct TxUpdateRequest {
     description: String,
-    date: DateTime<Utc>,
-    amount: f32,
+    payee: String,
+    date: NaiveDate,
+    #[serde(deserialize_with = "deserialize_optional")]
+    value_date: Option<NaiveDate>,
+    amount: Money,
     #[serde(deserialize_with = "deserialize_optional")]
     category: Option<i32>,
+    status: TransactionStatus,
+    #[serde(default)]
+    tags: String,
+    #[serde(default, deserialize_with = "deserialize_optional")]
+    transfer_account: Option<i32>,
+    /// Unchecked checkboxes are not sent.
+    #[serde(default)]
+    needs_review: bool,
+}
+
+async fn apply_update(
+    pool: &SqlitePool,
+    tx: &mut Transaction,
+    req: &TxUpdateRequest,
+) -> sqlx::Result<()> {
+    // Unlock first, so a reconciled transaction can be reopened and edited in one go.
+    if tx.is_locked() && req.status != TransactionStatus::Reconciled {
+        tx.set_status(pool, req.status).await?;
+    }
+
+    if tx.get_amount() != req.amount {
+        tx.set_amount(pool, req.amount).await?;
+    }
+
+    if tx.get_date() != req.date {
+        tx.set_date(pool, req.date).await?;
+    }
+
+    let payee = Some(req.payee.trim()).filter(|x| !x.is_empty());
+    if tx.get_payee() != payee {
+        tx.set_payee(pool, payee).await?;
+    }
+
+    if tx.get_value_date() != req.value_date {
+        tx.set_value_date(pool, req.value_date).await?;
+    }
+
+    if tx.get_description() != req.description {
+        tx.set_description(pool, &req.description).await?;
+    }
+
+    if tx.get_category() != req.category {
+        tx.set_category(pool, req.category).await?;
+    }
+
+    if tx.get_transfer_account() != req.transfer_account {
+        tx.set_transfer_account(pool, req.transfer_account).await?;
+    }
+
+    let mut tags: Vec<&str> = req
+        .tags
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect();
+    tags.sort_unstable();
+    tags.dedup();
+    if tx.get_tags(pool).await? != tags {
+        tx.set_tags(pool, &tags).await?;
+    }
+
+    if tx.needs_review() != req.needs_review {
+        tx.set_needs_review(pool, req.needs_review).await?;
+    }
+
+    if tx.get_status() != req.status {
+        tx.set_status(pool, req.status).await?;
+    }
+
+    Ok(())
 }
 
 pub async fn update(
@@ -64,26 +176,18 @@ pub async fn update(
         Err(e) => {
             return (
                 StatusCode::NOT_FOUND,
-                [(header::LOCATION, ret_str)],
+                [(header::CONTENT_TYPE, String::from("text/plain"))],
                 format!("{e:?}"),
             );
         }
     };
 
-    let amount = (req.amount * 100.0).round() as i32;
-
-    if tx.get_amount() != amount {
-        tx.set_amount(db.as_ref(), amount).await.unwrap();
-    }
-
-    if tx.get_description() != req.description {
-        tx.set_description(db.as_ref(), &req.description)
-            .await
-            .unwrap();
-    }
-
-    if tx.get_category() != req.category {
-        tx.set_category(db.as_ref(), req.category).await.unwrap();
+    if let Err(e) = apply_update(db.as_ref(), &mut tx, &req).await {
+        return (
+            StatusCode::BAD_REQUEST,
+            [(header::CONTENT_TYPE, String::from("text/plain"))],
+            format!("{e}"),
+        );
     }
 
     (
@@ -92,3 +196,30 @@ pub async fn update(
         String::new(),
     )
 }
+
+pub async fn delete(db: State<Arc<SqlitePool>>, Path(id): Path<i32>) -> impl IntoResponse {
+    let tx = match Transaction::get_by_id(db.as_ref(), id).await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                [(header::CONTENT_TYPE, String::from("text/plain"))],
+                format!("{e:?}"),
+            );
+        }
+    };
+
+    let ret_str = format!("/accounts/id/{}", tx.get_account());
+    match tx.delete(db.as_ref()).await {
+        Ok(_) => (
+            StatusCode::MOVED_PERMANENTLY,
+            [(header::LOCATION, ret_str)],
+            String::new(),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(header::CONTENT_TYPE, String::from("text/plain"))],
+            format!("{e:?}"),
+        ),
+    }
+}
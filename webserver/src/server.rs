@@ -4,9 +4,11 @@ use std::sync::Arc;
 use hyper::{header, StatusCode};
 use sqlx::SqlitePool;
 
+use accounters::models::{rule_engine::RuleCache, scheduled::ScheduledTransaction};
+
 use axum::{
     extract::FromRef,
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use tera::Tera;
@@ -81,6 +83,21 @@ pub async fn start_server(bind: &str, db_url: &str) -> Result<(), ServerError> {
     tmpls
         .add_raw_template("rules_new_success.html", templates::RULES_NEW_SUCCESS)
         .unwrap();
+    tmpls
+        .add_raw_template("scheduled.html", templates::SCHEDULED)
+        .unwrap();
+    tmpls
+        .add_raw_template("subscriptions.html", templates::SUBSCRIPTIONS)
+        .unwrap();
+    tmpls
+        .add_raw_template("goals.html", templates::GOALS)
+        .unwrap();
+    tmpls
+        .add_raw_template("loans.html", templates::LOANS)
+        .unwrap();
+    tmpls
+        .add_raw_template("loan.html", templates::LOAN)
+        .unwrap();
     tmpls
         .add_raw_template("transaction.html", templates::TRANSACTION)
         .unwrap();
@@ -89,8 +106,11 @@ pub async fn start_server(bind: &str, db_url: &str) -> Result<(), ServerError> {
     let state = AppState {
         db: Arc::new(db),
         tmpls: Arc::new(tmpls),
+        rules: Arc::new(RuleCache::default()),
     };
 
+    tokio::spawn(create_scheduled_transactions(state.db.clone()));
+
     let exec_id: u32 = rand::random();
 
     let app = Router::new()
@@ -108,10 +128,32 @@ pub async fn start_server(bind: &str, db_url: &str) -> Result<(), ServerError> {
                     "/accounts/id/:id/transactions",
                     get(routes::ui::account::list_transactions),
                 )
+                .route(
+                    "/accounts/id/:id/trades",
+                    post(routes::ui::investments::new_trade_action),
+                )
                 .route(
                     "/transaction/:id",
                     get(routes::ui::transaction::view).post(routes::ui::transaction::update),
                 )
+                .route(
+                    "/transaction/:id/delete",
+                    post(routes::ui::transaction::delete),
+                )
+                .route(
+                    "/scheduled",
+                    get(routes::ui::scheduled::view).post(routes::ui::scheduled::new_action),
+                )
+                .route("/subscriptions", get(routes::ui::subscriptions::view))
+                .route(
+                    "/goals",
+                    get(routes::ui::goals::view).post(routes::ui::goals::new_action),
+                )
+                .route(
+                    "/loans",
+                    get(routes::ui::loans::list_view).post(routes::ui::loans::new_action),
+                )
+                .route("/loans/id/:id", get(routes::ui::loans::view))
                 .route(
                     "/classifiers",
                     get(routes::ui::classifier::view_classifiers),
@@ -121,6 +163,14 @@ pub async fn start_server(bind: &str, db_url: &str) -> Result<(), ServerError> {
                     get(routes::ui::classifier::rules_new_view)
                         .post(routes::ui::classifier::rules_new_action),
                 )
+                .route(
+                    "/classifiers/rules/:id/conditions",
+                    post(routes::ui::classifier::condition_new_action),
+                )
+                .route(
+                    "/classifiers/rules/:id/actions",
+                    post(routes::ui::classifier::action_new_action),
+                )
                 .route(
                     "/classifiers/new_category",
                     get(routes::ui::classifier::category_new_view)
@@ -154,7 +204,29 @@ pub async fn start_server(bind: &str, db_url: &str) -> Result<(), ServerError> {
                 .route("/login", post(routes::api::login))
                 .route("/accounts", post(routes::api::accounts::account_create))
                 .route("/accounts", get(routes::api::accounts::account_list))
-                .route("/accounts/id/:id", get(routes::api::accounts::account_get))
+                .route(
+                    "/accounts/id/:id",
+                    get(routes::api::accounts::account_get)
+                        .post(routes::api::accounts::account_update)
+                        .delete(routes::api::accounts::account_delete),
+                )
+                .route(
+                    "/accounts/id/:id/balances",
+                    get(routes::api::accounts::account_balances),
+                )
+                .route(
+                    "/accounts/id/:id/forecast",
+                    get(routes::api::accounts::account_forecast),
+                )
+                .route(
+                    "/accounts/id/:id/archive",
+                    post(routes::api::accounts::account_archive),
+                )
+                .route(
+                    "/accounts/id/:id/unarchive",
+                    post(routes::api::accounts::account_unarchive),
+                )
+                .route("/net_worth", get(routes::api::accounts::net_worth))
                 .route(
                     "/accounts/id/:id/transaction",
                     post(routes::api::transactions::create),
@@ -163,14 +235,97 @@ pub async fn start_server(bind: &str, db_url: &str) -> Result<(), ServerError> {
                     "/accounts/id/:id/transaction",
                     get(routes::api::transactions::list),
                 )
+                .route(
+                    "/transactions/id/:id",
+                    delete(routes::api::transactions::delete),
+                )
+                .route(
+                    "/transactions/id/:id/move",
+                    post(routes::api::transactions::move_within_day),
+                )
                 .route(
                     "/accounts/id/:id/recategorize",
                     post(routes::api::accounts::recategorize),
                 )
+                .route(
+                    "/accounts/recategorize",
+                    post(routes::api::accounts::recategorize_all),
+                )
                 .route("/categories", post(routes::api::categories::create))
                 .route("/categories", get(routes::api::categories::list))
+                .route("/scheduled", post(routes::api::scheduled::create))
+                .route("/scheduled", get(routes::api::scheduled::list))
+                .route(
+                    "/scheduled/id/:id",
+                    delete(routes::api::scheduled::delete),
+                )
+                .route("/subscriptions", get(routes::api::subscriptions::list))
+                .route("/budgets", post(routes::api::budgets::create))
+                .route("/budgets", get(routes::api::budgets::list))
+                .route(
+                    "/budgets/id/:id",
+                    post(routes::api::budgets::update).delete(routes::api::budgets::delete),
+                )
+                .route(
+                    "/accounts/id/:id/trades",
+                    get(routes::api::investments::trade_list)
+                        .post(routes::api::investments::trade_create),
+                )
+                .route(
+                    "/accounts/id/:id/portfolio",
+                    get(routes::api::investments::portfolio),
+                )
+                .route(
+                    "/trades/id/:id",
+                    delete(routes::api::investments::trade_delete),
+                )
+                .route(
+                    "/securities",
+                    get(routes::api::investments::security_list)
+                        .post(routes::api::investments::security_create),
+                )
+                .route(
+                    "/securities/prices",
+                    post(routes::api::investments::prices_import),
+                )
+                .route("/loans", post(routes::api::loans::create))
+                .route("/loans", get(routes::api::loans::list))
+                .route(
+                    "/loans/id/:id",
+                    get(routes::api::loans::get).delete(routes::api::loans::delete),
+                )
+                .route("/goals", post(routes::api::goals::create))
+                .route("/goals", get(routes::api::goals::list))
+                .route("/goals/id/:id", delete(routes::api::goals::delete))
+                .route("/audit", get(routes::api::audit::list))
                 .route("/rules", post(routes::api::rules::create))
-                .route("/rules", get(routes::api::rules::list)),
+                .route("/rules", get(routes::api::rules::list))
+                .route("/rules/preview", post(routes::api::rules::preview))
+                .route(
+                    "/rules/id/:id",
+                    post(routes::api::rules::update).delete(routes::api::rules::delete),
+                )
+                .route("/rules/id/:id/toggle", post(routes::api::rules::toggle))
+                .route(
+                    "/rules/id/:id/move",
+                    post(routes::api::rules::move_in_order),
+                )
+                .route(
+                    "/rules/id/:id/conditions",
+                    post(routes::api::rules::condition_create),
+                )
+                .route(
+                    "/rules/conditions/id/:id",
+                    delete(routes::api::rules::condition_delete),
+                )
+                .route(
+                    "/rules/id/:id/actions",
+                    post(routes::api::rules::action_create),
+                )
+                .route(
+                    "/rules/actions/id/:id",
+                    delete(routes::api::rules::action_delete),
+                ),
         )
         .with_state(state);
 
@@ -182,10 +337,25 @@ pub async fn start_server(bind: &str, db_url: &str) -> Result<(), ServerError> {
     Ok(())
 }
 
+/// Creates the transactions of every schedule as they become due.
+async fn create_scheduled_transactions(db: Arc<SqlitePool>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+    loop {
+        interval.tick().await;
+        let today = chrono::Utc::now().date_naive();
+        match ScheduledTransaction::create_all_due(db.as_ref(), today).await {
+            Ok(0) => {}
+            Ok(n) => println!("Created {n} scheduled transactions"),
+            Err(e) => println!("Error creating scheduled transactions: {e:?}"),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub db: Arc<SqlitePool>,
     pub tmpls: Arc<Tera>,
+    pub rules: Arc<RuleCache>,
 }
 
 impl FromRef<AppState> for Arc<SqlitePool> {
@@ -199,3 +369,9 @@ impl FromRef<AppState> for Arc<Tera> {
         state.tmpls.clone()
     }
 }
+
+impl FromRef<AppState> for Arc<RuleCache> {
+    fn from_ref(state: &AppState) -> Arc<RuleCache> {
+        state.rules.clone()
+    }
+}
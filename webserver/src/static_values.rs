@@ -7,6 +7,11 @@ pub const CATEGORIES_NEW: &str = include_str!("static/categories_new.html");
 pub const CLASSIFIERS: &str = include_str!("static/classifiers.html");
 pub const RULES_NEW: &str = include_str!("static/rules_new.html");
 pub const RULES_NEW_SUCCESS: &str = include_str!("static/rules_new_success.html");
+pub const SCHEDULED: &str = include_str!("static/scheduled.html");
+pub const GOALS: &str = include_str!("static/goals.html");
+pub const LOAN: &str = include_str!("static/loan.html");
+pub const LOANS: &str = include_str!("static/loans.html");
+pub const SUBSCRIPTIONS: &str = include_str!("static/subscriptions.html");
 pub const TRANSACTION: &str = include_str!("static/transaction.html");
 
 pub const STYLES: &str = include_str!("static/styles.css");